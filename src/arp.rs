@@ -0,0 +1,262 @@
+use crate::cancel::CancelToken;
+use crate::rate_limit::RateLimiter;
+use std::collections::HashMap;
+use std::io;
+use std::net::Ipv4Addr;
+use std::time::Duration;
+
+/// Broadcast an ARP request for each of `targets` on `interface` and collect
+/// the replies, as uppercase `AA:BB:CC:DD:EE:FF` MACs by address.
+///
+/// Finds hosts that drop ping, and their MAC in the same pass. Requests are
+/// sent at least `interval` apart, each in its own `limiter` slot. Needs
+/// Linux and `CAP_NET_RAW`; elsewhere, or without permission, an error is
+/// returned and the caller carries on without it. Cancelling returns the
+/// replies seen so far.
+pub fn sweep(
+    interface: &str,
+    source_ip: Ipv4Addr,
+    targets: &[Ipv4Addr],
+    interval: Duration,
+    limiter: &RateLimiter,
+    cancel: &CancelToken,
+) -> io::Result<HashMap<Ipv4Addr, String>> {
+    #[cfg(target_os = "linux")]
+    {
+        linux::sweep(interface, source_ip, targets, interval, limiter, cancel)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = (interface, source_ip, targets, interval, limiter, cancel);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "ARP sweep is only supported on Linux"))
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use std::ffi::CString;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::time::Instant;
+
+    // How long to keep listening for replies after the last request
+    const REPLY_WAIT: Duration = Duration::from_secs(1);
+
+    const ETHERTYPE_ARP: u16 = 0x0806;
+    const ARP_REQUEST: u16 = 1;
+    const ARP_REPLY: u16 = 2;
+
+    // Ethernet header plus an IPv4-over-Ethernet ARP body
+    fn request_frame(source_mac: [u8; 6], source_ip: Ipv4Addr, target: Ipv4Addr) -> [u8; 42] {
+        let mut frame = [0u8; 42];
+        frame[0..6].copy_from_slice(&[0xFF; 6]);
+        frame[6..12].copy_from_slice(&source_mac);
+        frame[12..14].copy_from_slice(&ETHERTYPE_ARP.to_be_bytes());
+
+        frame[14..16].copy_from_slice(&1u16.to_be_bytes()); // Ethernet
+        frame[16..18].copy_from_slice(&0x0800u16.to_be_bytes()); // IPv4
+        frame[18] = 6;
+        frame[19] = 4;
+        frame[20..22].copy_from_slice(&ARP_REQUEST.to_be_bytes());
+        frame[22..28].copy_from_slice(&source_mac);
+        frame[28..32].copy_from_slice(&source_ip.octets());
+        // Target hardware address stays zero
+        frame[38..42].copy_from_slice(&target.octets());
+        frame
+    }
+
+    // Sender address and MAC of an ARP reply frame sent to `source_ip`.
+    // Gratuitous replies and those to other hosts are dropped.
+    fn parse_reply(frame: &[u8], source_ip: Ipv4Addr) -> Option<(Ipv4Addr, String)> {
+        if frame.len() < 42
+            || frame[12..14] != ETHERTYPE_ARP.to_be_bytes()
+            || frame[20..22] != ARP_REPLY.to_be_bytes()
+            || frame[38..42] != source_ip.octets()
+        {
+            return None;
+        }
+
+        let ip = Ipv4Addr::new(frame[28], frame[29], frame[30], frame[31]);
+        let mac = frame[22..28]
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":");
+        Some((ip, mac))
+    }
+
+    pub fn sweep(
+        interface: &str,
+        source_ip: Ipv4Addr,
+        targets: &[Ipv4Addr],
+        interval: Duration,
+        limiter: &RateLimiter,
+        cancel: &CancelToken,
+    ) -> io::Result<HashMap<Ipv4Addr, String>> {
+        let source_mac = interface_mac(interface)?;
+        let name = CString::new(interface).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let protocol = ETHERTYPE_ARP.to_be();
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_PACKET,
+                libc::SOCK_RAW | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+                protocol as libc::c_int,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        // Bound to the interface; also used as the broadcast destination
+        let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+        addr.sll_family = libc::AF_PACKET as u16;
+        addr.sll_protocol = protocol;
+        addr.sll_ifindex = ifindex as i32;
+        addr.sll_halen = 6;
+        addr.sll_addr[..6].copy_from_slice(&[0xFF; 6]);
+        let addr_ptr = &addr as *const libc::sockaddr_ll as *const libc::sockaddr;
+        let addr_len = std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t;
+
+        if unsafe { libc::bind(fd.as_raw_fd(), addr_ptr, addr_len) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let wanted: std::collections::HashSet<&Ipv4Addr> = targets.iter().collect();
+        let mut found = HashMap::new();
+
+        for target in targets {
+            if limiter.wait_blocking(cancel) {
+                return Ok(found);
+            }
+            let frame = request_frame(source_mac, source_ip, *target);
+            let sent = unsafe {
+                libc::sendto(
+                    fd.as_raw_fd(),
+                    frame.as_ptr() as *const libc::c_void,
+                    frame.len(),
+                    0,
+                    addr_ptr,
+                    addr_len,
+                )
+            };
+            if sent < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            drain(&fd, source_ip, &wanted, &mut found);
+            if cancel.sleep(interval) {
+                return Ok(found);
+            }
+        }
+
+        let start = Instant::now();
+        while start.elapsed() < REPLY_WAIT {
+            drain(&fd, source_ip, &wanted, &mut found);
+            if cancel.sleep(Duration::from_millis(10)) {
+                break;
+            }
+        }
+
+        Ok(found)
+    }
+
+    // Read every queued frame without blocking
+    fn drain(
+        fd: &OwnedFd,
+        source_ip: Ipv4Addr,
+        wanted: &std::collections::HashSet<&Ipv4Addr>,
+        found: &mut HashMap<Ipv4Addr, String>,
+    ) {
+        let mut buf = [0u8; 128];
+        loop {
+            let n = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+            if n <= 0 {
+                break;
+            }
+            if let Some((ip, mac)) = parse_reply(&buf[..n as usize], source_ip) {
+                if wanted.contains(&ip) {
+                    found.insert(ip, mac);
+                }
+            }
+        }
+    }
+
+    fn interface_mac(interface: &str) -> io::Result<[u8; 6]> {
+        let text = std::fs::read_to_string(format!("/sys/class/net/{}/address", interface))?;
+        let bytes: Vec<u8> = text
+            .trim()
+            .split(':')
+            .filter_map(|part| u8::from_str_radix(part, 16).ok())
+            .collect();
+        bytes
+            .try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "interface has no Ethernet address"))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const OUR_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+        const OUR_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+
+        // What a device at `sender` answers to our request
+        fn reply_to(request: &[u8; 42], sender_mac: [u8; 6], sender: Ipv4Addr) -> [u8; 42] {
+            let mut reply = *request;
+            reply[0..6].copy_from_slice(&request[6..12]);
+            reply[6..12].copy_from_slice(&sender_mac);
+            reply[20..22].copy_from_slice(&ARP_REPLY.to_be_bytes());
+            reply[22..28].copy_from_slice(&sender_mac);
+            reply[28..32].copy_from_slice(&sender.octets());
+            reply[32..38].copy_from_slice(&request[22..28]);
+            reply[38..42].copy_from_slice(&request[28..32]);
+            reply
+        }
+
+        #[test]
+        fn request_layout() {
+            let target = Ipv4Addr::new(192, 168, 1, 20);
+            let frame = request_frame(OUR_MAC, OUR_IP, target);
+            assert_eq!(frame[0..6], [0xFF; 6]);
+            assert_eq!(frame[6..12], OUR_MAC);
+            assert_eq!(frame[12..14], ETHERTYPE_ARP.to_be_bytes());
+            assert_eq!(frame[20..22], ARP_REQUEST.to_be_bytes());
+            assert_eq!(frame[28..32], OUR_IP.octets());
+            assert_eq!(frame[32..38], [0; 6]);
+            assert_eq!(frame[38..42], target.octets());
+            // Our own request isn't a reply
+            assert_eq!(parse_reply(&frame, OUR_IP), None);
+        }
+
+        #[test]
+        fn reply_round_trip() {
+            let target = Ipv4Addr::new(192, 168, 1, 20);
+            let reply = reply_to(&request_frame(OUR_MAC, OUR_IP, target), [0x78, 0x8a, 0x20, 0xaa, 0xbb, 0xcc], target);
+            assert_eq!(parse_reply(&reply, OUR_IP), Some((target, String::from("78:8A:20:AA:BB:CC"))));
+            assert_eq!(parse_reply(&reply[..41], OUR_IP), None);
+        }
+
+        #[test]
+        fn rejects_other_ethertypes() {
+            let target = Ipv4Addr::new(192, 168, 1, 20);
+            let mut reply = reply_to(&request_frame(OUR_MAC, OUR_IP, target), [0x78, 0x8a, 0x20, 0xaa, 0xbb, 0xcc], target);
+            reply[12..14].copy_from_slice(&0x0800u16.to_be_bytes());
+            assert_eq!(parse_reply(&reply, OUR_IP), None);
+        }
+
+        #[test]
+        fn rejects_replies_to_another_host() {
+            let target = Ipv4Addr::new(192, 168, 1, 20);
+            let other = Ipv4Addr::new(192, 168, 1, 11);
+            let reply = reply_to(&request_frame(OUR_MAC, other, target), [0x78, 0x8a, 0x20, 0xaa, 0xbb, 0xcc], target);
+            assert_eq!(parse_reply(&reply, OUR_IP), None);
+        }
+    }
+}
@@ -0,0 +1,385 @@
+// Headless front-end for jump boxes and field laptops without a display.
+//
+// Usage:
+//   unifi-adopt-cli scan [START END | TARGETS]
+//   unifi-adopt-cli adopt [options] <ip[:port]>...
+//   unifi-adopt-cli adopt [options] --range START END
+//   unifi-adopt-cli adopt [options] --targets TARGETS
+
+use std::process::ExitCode;
+use unifi_adopt::config::{Credentials, HostKeyPolicy};
+use unifi_adopt::{config, known_hosts, network_interface, network_scanner, ssh_handler};
+use unifi_adopt::{AdoptionError, AdoptionOptions, AdoptionReport, CancelToken, Device, ScanOptions, TargetSet};
+
+// Exit codes
+const EXIT_OK: u8 = 0;
+const EXIT_ADOPTION_FAILED: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+const EXIT_SCAN_FAILED: u8 = 3;
+const EXIT_NO_DEVICES: u8 = 4;
+const EXIT_HOST_KEY_MISMATCH: u8 = 5;
+const EXIT_UNCONFIRMED: u8 = 6;
+
+const USAGE: &str = "\
+Usage:
+  unifi-adopt-cli scan [START END | TARGETS]
+  unifi-adopt-cli adopt [options] <ip[:port]>...
+  unifi-adopt-cli adopt [options] --range START END
+  unifi-adopt-cli adopt [options] --targets TARGETS
+
+TARGETS is a comma-separated list of hosts (10.0.0.5), ranges (10.0.0.10-50
+or 10.0.0.10-10.0.1.20), CIDR blocks (10.0.8.0/22) and exclusions of any of
+those prefixed with ! (!10.0.8.1). Without one, scan uses the local subnet.
+
+A device's SSH port can be given with its address (10.0.0.5:2222,
+[fd00::5]:2222). Otherwise the credential set's port is used, then --port.
+
+Options:
+  --controller URL     Controller URL (default: from config)
+  --credential NAME    Only try the named credential set from config
+                       (repeatable, tried in the order given)
+  --username USER      SSH username for a command-line credential set
+  --password PASS      SSH password for the command-line set
+  --key PATH           SSH private key for the command-line set
+  --passphrase PASS    Passphrase for --key
+  --agent              Try keys from the running ssh-agent first
+  --port PORT          SSH port for devices without one (default: from config)
+  --host-key-policy P  strict, tofu or off (default: from config)
+  --accept-changed-host-key
+                       Trust a device's new host key after a factory reset
+  --verify-timeout S   Seconds to wait for the device to reach the controller
+  --no-verify          Don't wait for the device to reach the controller
+  --reinform S         Re-send set-inform every S seconds until the device is
+                       connected (approve it in the controller meanwhile)
+
+The command-line credential set, if any, is tried before the sets from config.
+
+Exit codes:
+  0  every device was adopted (or the scan succeeded)
+  1  one or more devices failed adoption
+  2  invalid arguments
+  3  the network scan failed
+  4  no SSH-enabled devices were found in the range
+  5  a device's host key did not match known_hosts
+  6  inform was sent but a device never confirmed reaching the controller";
+
+struct AdoptArgs {
+    options: AdoptionOptions,
+    accept_changed_host_key: bool,
+    targets: Vec<String>,
+    // Scan these and adopt every SSH-enabled host found
+    scan: Option<TargetSet>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let code = match args.first().map(String::as_str) {
+        Some("scan") => run_scan(&args[1..]).await,
+        Some("adopt") => match parse_adopt_args(&args[1..]) {
+            Ok(opts) => run_adopt(opts).await,
+            Err(e) => usage_error(&e),
+        },
+        Some("-h") | Some("--help") | Some("help") => {
+            println!("{}", USAGE);
+            EXIT_OK
+        }
+        Some(other) => usage_error(&format!("Unknown command: {}", other)),
+        None => usage_error("Missing command"),
+    };
+
+    ExitCode::from(code)
+}
+
+fn usage_error(message: &str) -> u8 {
+    eprintln!("{}\n\n{}", message, USAGE);
+    EXIT_USAGE
+}
+
+async fn run_scan(args: &[String]) -> u8 {
+    let targets = match resolve_targets(args) {
+        Ok(targets) => targets,
+        Err(e) => return usage_error(&e),
+    };
+
+    match scan(&targets, config::load_config().ssh_port).await {
+        Ok(devices) => {
+            for device in &devices {
+                let rtt = device
+                    .rtt
+                    .map(|rtt| format!("{:.1}ms", rtt.as_secs_f64() * 1000.0))
+                    .unwrap_or_else(|| String::from("-"));
+                let mut line = format!(
+                    "{:<16} {:<18} {:<4} {:>8} {:<24} {}",
+                    device.ssh_target(),
+                    device.mac,
+                    if device.has_ssh { "ssh" } else { "-" },
+                    rtt,
+                    device.hostname.as_deref().unwrap_or("-"),
+                    device.company
+                );
+                let kind = device.kind().label();
+                if !kind.is_empty() {
+                    line.push_str(&format!(" ({})", kind));
+                }
+                if let Some(found) = &device.discovery {
+                    let model = if found.model.is_empty() { &found.platform } else { &found.model };
+                    line.push_str(&format!(" [{} {}", model, found.display_version()));
+                    match found.is_default {
+                        Some(true) => line.push_str(", factory default]"),
+                        Some(false) => line.push_str(", adopted]"),
+                        None => line.push(']'),
+                    }
+                }
+                if !device.ports.is_empty() {
+                    let ports: Vec<String> = device.ports.iter().map(|p| p.to_string()).collect();
+                    line.push_str(&format!(" ports: {}", ports.join(", ")));
+                }
+                println!("{}", line);
+            }
+            println!("{} device(s) found", devices.len());
+            EXIT_OK
+        }
+        Err(e) => {
+            eprintln!("Scan failed: {}", e);
+            EXIT_SCAN_FAILED
+        }
+    }
+}
+
+async fn run_adopt(args: AdoptArgs) -> u8 {
+    // (ip, mac) pairs; the MAC is only known for scanned devices
+    let mut targets: Vec<(String, Option<String>)> =
+        args.targets.iter().map(|ip| (ip.clone(), None)).collect();
+
+    if let Some(scan_targets) = &args.scan {
+        match scan(scan_targets, args.options.ssh_port).await {
+            Ok(devices) => {
+                targets.extend(
+                    devices
+                        .into_iter()
+                        .filter(|d| d.has_ssh)
+                        .map(|d| (d.ssh_target(), Some(d.mac))),
+                );
+            }
+            Err(e) => {
+                eprintln!("Scan failed: {}", e);
+                return EXIT_SCAN_FAILED;
+            }
+        }
+        if targets.is_empty() {
+            eprintln!("No SSH-enabled devices found in {}", scan_targets);
+            return EXIT_NO_DEVICES;
+        }
+    }
+
+    let mut failed = Vec::new();
+    let mut unconfirmed = 0;
+    let mut host_key_mismatch = false;
+    for (ip, mac) in &targets {
+        println!("==> Adopting {}", ip);
+
+        let mut result = adopt(ip, mac, &args.options).await;
+
+        if let Err(AdoptionError::HostKeyMismatch { address, presented, .. }) = &result {
+            if args.accept_changed_host_key {
+                println!("\n==> {}: trusting new host key {}", ip, presented);
+                known_hosts::trust(address, mac.as_deref(), presented);
+                result = adopt(ip, mac, &args.options).await;
+            }
+        }
+
+        match result {
+            Ok(report) => {
+                let outcome = if report.confirmed == Some(false) {
+                    unconfirmed += 1;
+                    "SENT (unconfirmed)"
+                } else {
+                    "OK"
+                };
+                match report.info {
+                    Some(info) => println!(
+                        "\n==> {}: {} ({} {}, {})",
+                        ip, outcome, info.model, info.version, info.status
+                    ),
+                    None => println!("\n==> {}: {}", ip, outcome),
+                }
+            }
+            Err(err) => {
+                // The error message is the last line of the returned log
+                let reason = err.log().lines().last().unwrap_or("Unknown error");
+                println!("\n==> {}: FAILED ({})", ip, reason);
+                if matches!(err, AdoptionError::HostKeyMismatch { .. }) {
+                    host_key_mismatch = true;
+                }
+                failed.push(ip.clone());
+            }
+        }
+    }
+
+    println!(
+        "{} adopted, {} unconfirmed, {} failed",
+        targets.len() - failed.len() - unconfirmed,
+        unconfirmed,
+        failed.len()
+    );
+
+    if host_key_mismatch {
+        EXIT_HOST_KEY_MISMATCH
+    } else if !failed.is_empty() {
+        EXIT_ADOPTION_FAILED
+    } else if unconfirmed > 0 {
+        EXIT_UNCONFIRMED
+    } else {
+        EXIT_OK
+    }
+}
+
+async fn adopt(ip: &str, mac: &Option<String>, options: &AdoptionOptions) -> Result<AdoptionReport, AdoptionError> {
+    let ip = ip.to_string();
+    let mac = mac.clone();
+    let options = options.clone();
+
+    // ssh_handler echoes the session to stdout as it runs
+    tokio::task::spawn_blocking(move || {
+        ssh_handler::execute_adoption(&ip, mac.as_deref(), &options, None, &CancelToken::new())
+    })
+    .await
+    .unwrap_or_else(|e| Err(AdoptionError::Failed(format!("Adoption task panicked: {}", e))))
+}
+
+// `ssh_port` is probed as well as the configured ports
+async fn scan(targets: &TargetSet, ssh_port: u16) -> Result<Vec<Device>, String> {
+    println!("Scanning {} ({} addresses)...", targets, targets.len());
+    let mut options = ScanOptions::from_config(&config::load_config());
+    if !options.ports.contains(&ssh_port) {
+        options.ports.push(ssh_port);
+    }
+    network_scanner::scan_network(targets, &options, &CancelToken::new(), None).await
+}
+
+// `START END` as two addresses, or a TARGETS list (spaces allowed between
+// items); the local subnet when empty
+fn resolve_targets(args: &[String]) -> Result<TargetSet, String> {
+    match args {
+        [] => network_interface::get_default_network()
+            .ok_or_else(|| String::from("Could not detect a local network, pass START END or TARGETS"))
+            .and_then(|net| TargetSet::parse(&net.cidr)),
+        [start, end] => match (start.parse(), end.parse()) {
+            (Ok(start), Ok(end)) => range_targets(start, end),
+            _ => TargetSet::parse(&args.join(",")),
+        },
+        _ => TargetSet::parse(&args.join(",")),
+    }
+}
+
+fn range_targets(start: std::net::Ipv4Addr, end: std::net::Ipv4Addr) -> Result<TargetSet, String> {
+    TargetSet::parse(&format!("{}-{}", start, end))
+}
+
+fn parse_adopt_args(args: &[String]) -> Result<AdoptArgs, String> {
+    let config = config::load_config();
+
+    let mut opts = AdoptArgs {
+        options: AdoptionOptions::from_config(&config),
+        accept_changed_host_key: false,
+        targets: Vec::new(),
+        scan: None,
+    };
+    opts.options.credentials.clear();
+    let mut cli_creds = Credentials::with_password("command line", "", "");
+    let mut selected: Vec<String> = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| {
+            iter.next()
+                .cloned()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+
+        match arg.as_str() {
+            "--controller" => opts.options.controller_url = value("--controller")?,
+            "--credential" => selected.push(value("--credential")?),
+            "--username" => cli_creds.username = value("--username")?,
+            "--password" => cli_creds.password = value("--password")?,
+            "--key" => cli_creds.key_path = value("--key")?,
+            "--passphrase" => cli_creds.key_passphrase = value("--passphrase")?,
+            "--agent" => cli_creds.use_agent = true,
+            "--port" => {
+                let port = value("--port")?;
+                opts.options.ssh_port = port
+                    .parse()
+                    .ok()
+                    .filter(|&port| port > 0)
+                    .ok_or_else(|| format!("Invalid --port: {}", port))?;
+            }
+            "--host-key-policy" => {
+                let policy = value("--host-key-policy")?;
+                opts.options.host_key_policy = HostKeyPolicy::parse(&policy)
+                    .ok_or_else(|| format!("Unknown host key policy: {}", policy))?;
+            }
+            "--accept-changed-host-key" => opts.accept_changed_host_key = true,
+            "--verify-timeout" => {
+                let secs = value("--verify-timeout")?;
+                let secs = secs
+                    .parse()
+                    .map_err(|_| format!("Invalid --verify-timeout: {}", secs))?;
+                opts.options.verify_timeout = Some(std::time::Duration::from_secs(secs));
+            }
+            "--no-verify" => opts.options.verify_timeout = None,
+            "--reinform" => {
+                let secs = value("--reinform")?;
+                let secs = secs
+                    .parse()
+                    .map_err(|_| format!("Invalid --reinform: {}", secs))?;
+                opts.options.reinform_interval = Some(std::time::Duration::from_secs(secs));
+            }
+            "--range" => {
+                let start = value("--range")?;
+                let end = value("--range")?;
+                let (Ok(start), Ok(end)) = (start.parse(), end.parse()) else {
+                    return Err(format!("Invalid --range: {} {}", start, end));
+                };
+                opts.scan = Some(range_targets(start, end)?);
+            }
+            "--targets" => opts.scan = Some(TargetSet::parse(&value("--targets")?)?),
+            flag if flag.starts_with("--") => return Err(format!("Unknown option: {}", flag)),
+            ip => {
+                network_interface::split_host_port(ip)?;
+                opts.targets.push(ip.to_string());
+            }
+        }
+    }
+
+    // Re-informing happens inside the verification loop
+    if opts.options.reinform_interval.is_some() && opts.options.verify_timeout.is_none() {
+        opts.options.verify_timeout = Some(std::time::Duration::from_secs(config.verify_timeout_secs));
+    }
+
+    if opts.targets.is_empty() && opts.scan.is_none() {
+        return Err(String::from("adopt needs at least one IP address, --range or --targets"));
+    }
+
+    let has_cli_creds = !cli_creds.password.is_empty() || !cli_creds.key_path.is_empty() || cli_creds.use_agent;
+    if has_cli_creds && cli_creds.username.is_empty() {
+        return Err(String::from("--username is required with --password, --key or --agent"));
+    }
+    if !cli_creds.username.is_empty() {
+        opts.options.credentials.push(cli_creds);
+    }
+
+    if selected.is_empty() {
+        opts.options.credentials.extend(config.credentials);
+    } else {
+        for name in &selected {
+            match config.credentials.iter().find(|c| &c.name == name) {
+                Some(creds) => opts.options.credentials.push(creds.clone()),
+                None => return Err(format!("No credential set named '{}' in config", name)),
+            }
+        }
+    }
+
+    Ok(opts)
+}
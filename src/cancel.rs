@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Shared flag for stopping a scan or an adoption from another task.
+///
+/// Clones share state, so the UI keeps one clone and hands another to the
+/// work it may want to stop. The SSH handler polls it between steps from its
+/// blocking thread; the scanner awaits [`CancelToken::cancelled`].
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+// How often a blocking wait re-checks the flag
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolve once [`CancelToken::cancel`] has been called.
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking so a cancel in between isn't missed
+            let notified = self.inner.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Block the current thread for `duration`, waking early on cancel.
+    /// Returns true if the token was cancelled.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let start = Instant::now();
+        while !self.is_cancelled() {
+            let elapsed = start.elapsed();
+            if elapsed >= duration {
+                return false;
+            }
+            std::thread::sleep(POLL_INTERVAL.min(duration - elapsed));
+        }
+        true
+    }
+}
@@ -1,28 +1,142 @@
 use std::fs;
 use std::path::PathBuf;
 
+/// Settings persisted to `config.txt` in the platform config directory.
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub controller_url: String,
-    pub ssh_username: String,
-    pub ssh_password: String,
-    pub alt_ssh_username: String,
-    pub alt_ssh_password: String,
+    /// Credential sets, tried in order until one authenticates.
+    pub credentials: Vec<Credentials>,
+    /// SSH port for devices whose address and credential set don't name
+    /// one.
+    pub ssh_port: u16,
+    pub host_key_policy: HostKeyPolicy,
+    /// Poll the device after `set-inform` until it reports reaching the
+    /// controller, for up to `verify_timeout_secs`.
+    pub verify_adoption: bool,
+    pub verify_timeout_secs: u64,
+    /// Keep re-sending `set-inform` every `reinform_interval_secs` until the
+    /// device is connected, for devices that need a second inform after
+    /// being approved in the controller.
+    pub reinform_until_connected: bool,
+    pub reinform_interval_secs: u64,
+    /// How many devices are adopted or identified at the same time; the
+    /// rest wait in the job queue.
+    pub max_parallel_jobs: usize,
+    /// Most addresses the network scanner probes at once.
+    pub scan_max_in_flight: usize,
+    /// Most packets a scan sends per second, all probes together; 0 means
+    /// no limit.
+    pub scan_probes_per_second: u32,
+    /// Also look for IPv6 devices on the scanned links.
+    pub scan_ipv6: bool,
+    /// dnsmasq or ISC dhcpd lease file to label scanned devices from; empty
+    /// for none.
+    pub dhcp_lease_file: String,
+    /// Ports the scanner probes on each live host; 10001 means the UniFi
+    /// discovery probe.
+    pub scan_ports: Vec<u16>,
+}
+
+/// What to do with a device's SSH host key before sending it credentials.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HostKeyPolicy {
+    /// Only connect to devices whose key is already in known_hosts.
+    Strict,
+    /// Trust on first use: remember new keys, refuse changed ones.
+    #[default]
+    Tofu,
+    /// Don't check host keys.
+    Off,
+}
+
+impl HostKeyPolicy {
+    pub const ALL: [HostKeyPolicy; 3] = [HostKeyPolicy::Strict, HostKeyPolicy::Tofu, HostKeyPolicy::Off];
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            HostKeyPolicy::Strict => "strict",
+            HostKeyPolicy::Tofu => "tofu",
+            HostKeyPolicy::Off => "off",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "strict" => Some(HostKeyPolicy::Strict),
+            "tofu" => Some(HostKeyPolicy::Tofu),
+            "off" => Some(HostKeyPolicy::Off),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for HostKeyPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            HostKeyPolicy::Strict => "Strict (known keys only)",
+            HostKeyPolicy::Tofu => "Trust on first use",
+            HostKeyPolicy::Off => "Off (don't verify)",
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// A named way to log in to a device over SSH.
+///
+/// Empty strings mean "not set". When several methods are configured the
+/// handler tries ssh-agent, then the key file, then the password.
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub name: String,
+    pub username: String,
+    pub password: String,
+    pub key_path: String,
+    pub key_passphrase: String,
+    pub use_agent: bool,
+    /// SSH port to log in on with this set, overriding the global one.
+    /// A port in the device's address (`ip:port`) still wins.
+    pub port: Option<u16>,
+}
+
+impl Credentials {
+    pub fn with_password(name: &str, username: &str, password: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            username: username.to_string(),
+            password: password.to_string(),
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
             controller_url: String::from("http://192.168.1.1:8080"),
-            ssh_username: String::from("ubnt"),
-            ssh_password: String::from("ubnt"),
-            alt_ssh_username: String::from(""),
-            alt_ssh_password: String::from(""),
+            credentials: vec![Credentials::with_password("Default (ubnt)", "ubnt", "ubnt")],
+            ssh_port: 22,
+            host_key_policy: HostKeyPolicy::default(),
+            verify_adoption: true,
+            verify_timeout_secs: 60,
+            reinform_until_connected: false,
+            reinform_interval_secs: 20,
+            max_parallel_jobs: 8,
+            scan_max_in_flight: 64,
+            scan_probes_per_second: 0,
+            scan_ipv6: false,
+            dhcp_lease_file: String::new(),
+            scan_ports: crate::fingerprint::DEFAULT_PORTS.to_vec(),
         }
     }
 }
 
 fn get_config_path() -> PathBuf {
+    app_config_dir().join("config.txt")
+}
+
+/// Directory holding `config.txt` and the other files the app persists.
+pub fn app_config_dir() -> PathBuf {
     // Use platform-appropriate config directory
     // Windows: C:\Users\<user>\AppData\Roaming\UniFiAdoption
     // macOS: ~/Library/Application Support/UniFiAdoption
@@ -31,7 +145,7 @@ fn get_config_path() -> PathBuf {
     let app_dir = config_dir.join("UniFiAdoption");
     // Create directory if it doesn't exist
     let _ = fs::create_dir_all(&app_dir);
-    app_dir.join("config.txt")
+    app_dir
 }
 
 #[cfg(windows)]
@@ -59,6 +173,7 @@ fn get_config_dir() -> PathBuf {
         })
 }
 
+/// Load the config file, creating it with defaults on first run.
 pub fn load_config() -> AppConfig {
     let path = get_config_path();
     if !path.exists() {
@@ -69,27 +184,262 @@ pub fn load_config() -> AppConfig {
 
     let mut config = AppConfig::default();
     if let Ok(contents) = fs::read_to_string(&path) {
-        for line in contents.lines() {
-            if let Some((key, value)) = line.split_once('=') {
-                match key.trim() {
-                    "controller_url" => config.controller_url = value.trim().to_string(),
-                    "ssh_username" => config.ssh_username = value.trim().to_string(),
-                    "ssh_password" => config.ssh_password = value.trim().to_string(),
-                    "alt_ssh_username" => config.alt_ssh_username = value.trim().to_string(),
-                    "alt_ssh_password" => config.alt_ssh_password = value.trim().to_string(),
-                    _ => {}
+        config = parse_config(&contents);
+    }
+    config
+}
+
+// Credential sets are stored as `[credential]` blocks after the top-level keys.
+// The flat `ssh_*` / `alt_ssh_*` keys from older versions are migrated into
+// "Default" and "Alternate" sets.
+fn parse_config(contents: &str) -> AppConfig {
+    let mut config = AppConfig::default();
+    let mut credentials: Vec<Credentials> = Vec::new();
+    let mut legacy = Credentials::with_password("Default", "", "");
+    let mut legacy_alt = Credentials::with_password("Alternate", "", "");
+    let mut in_credential = false;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line == "[credential]" {
+            credentials.push(Credentials::default());
+            in_credential = true;
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+
+        if in_credential {
+            let creds = credentials.last_mut().unwrap();
+            match key.trim() {
+                "name" => creds.name = value,
+                "username" => creds.username = value,
+                "password" => creds.password = value,
+                "key_path" => creds.key_path = value,
+                "key_passphrase" => creds.key_passphrase = value,
+                "use_agent" => creds.use_agent = value == "true",
+                "port" => creds.port = value.parse().ok().filter(|&port| port > 0),
+                _ => {}
+            }
+            continue;
+        }
+
+        match key.trim() {
+            "controller_url" => config.controller_url = value,
+            "ssh_port" => {
+                if let Ok(port) = value.parse() {
+                    if port > 0 {
+                        config.ssh_port = port;
+                    }
+                }
+            }
+            "verify_adoption" => config.verify_adoption = value == "true",
+            "verify_timeout_secs" => {
+                if let Ok(secs) = value.parse() {
+                    config.verify_timeout_secs = secs;
+                }
+            }
+            "reinform_until_connected" => config.reinform_until_connected = value == "true",
+            "reinform_interval_secs" => {
+                if let Ok(secs) = value.parse() {
+                    config.reinform_interval_secs = secs;
                 }
             }
+            "max_parallel_jobs" => {
+                if let Ok(count) = value.parse() {
+                    config.max_parallel_jobs = count;
+                }
+            }
+            "scan_max_in_flight" => {
+                if let Ok(count) = value.parse() {
+                    config.scan_max_in_flight = count;
+                }
+            }
+            "scan_probes_per_second" => {
+                if let Ok(rate) = value.parse() {
+                    config.scan_probes_per_second = rate;
+                }
+            }
+            "scan_ipv6" => config.scan_ipv6 = value == "true",
+            "dhcp_lease_file" => config.dhcp_lease_file = value,
+            "scan_ports" => {
+                if let Ok(ports) = crate::fingerprint::parse_ports(&value) {
+                    config.scan_ports = ports;
+                }
+            }
+            "host_key_policy" => {
+                if let Some(policy) = HostKeyPolicy::parse(&value) {
+                    config.host_key_policy = policy;
+                }
+            }
+            "ssh_username" => legacy.username = value,
+            "ssh_password" => legacy.password = value,
+            "ssh_key_path" => legacy.key_path = value,
+            "ssh_key_passphrase" => legacy.key_passphrase = value,
+            "ssh_use_agent" => legacy.use_agent = value == "true",
+            "alt_ssh_username" => legacy_alt.username = value,
+            "alt_ssh_password" => legacy_alt.password = value,
+            "alt_ssh_key_path" => legacy_alt.key_path = value,
+            "alt_ssh_key_passphrase" => legacy_alt.key_passphrase = value,
+            "alt_ssh_use_agent" => legacy_alt.use_agent = value == "true",
+            _ => {}
         }
     }
+
+    if credentials.is_empty() {
+        for creds in [legacy, legacy_alt] {
+            if !creds.username.is_empty() {
+                credentials.push(creds);
+            }
+        }
+    }
+    if !credentials.is_empty() {
+        config.credentials = credentials;
+    }
+
     config
 }
 
+/// Write the config file. Errors are ignored.
 pub fn save_config(config: &AppConfig) {
-    let path = get_config_path();
-    let content = format!(
-        "controller_url={}\nssh_username={}\nssh_password={}\nalt_ssh_username={}\nalt_ssh_password={}\n",
-        config.controller_url, config.ssh_username, config.ssh_password, config.alt_ssh_username, config.alt_ssh_password
+    let _ = fs::write(get_config_path(), format_config(config));
+}
+
+// The config file contents, as `parse_config` reads them
+fn format_config(config: &AppConfig) -> String {
+    let mut content = format!(
+        "controller_url={}\nssh_port={}\nhost_key_policy={}\nverify_adoption={}\nverify_timeout_secs={}\n\
+         reinform_until_connected={}\nreinform_interval_secs={}\nmax_parallel_jobs={}\n\
+         scan_max_in_flight={}\nscan_probes_per_second={}\nscan_ipv6={}\n\
+         dhcp_lease_file={}\nscan_ports={}\n",
+        config.controller_url,
+        config.ssh_port,
+        config.host_key_policy.as_str(),
+        config.verify_adoption,
+        config.verify_timeout_secs,
+        config.reinform_until_connected,
+        config.reinform_interval_secs,
+        config.max_parallel_jobs,
+        config.scan_max_in_flight,
+        config.scan_probes_per_second,
+        config.scan_ipv6,
+        config.dhcp_lease_file,
+        crate::fingerprint::format_ports(&config.scan_ports),
     );
-    let _ = fs::write(path, content);
+    for creds in &config.credentials {
+        content.push_str(&format!(
+            "\n[credential]\nname={}\nusername={}\npassword={}\nkey_path={}\nkey_passphrase={}\nuse_agent={}\nport={}\n",
+            creds.name,
+            creds.username,
+            creds.password,
+            creds.key_path,
+            creds.key_passphrase,
+            creds.use_agent,
+            creds.port.map(|port| port.to_string()).unwrap_or_default(),
+        ));
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_keys_become_credential_sets() {
+        let config = parse_config(
+            "controller_url=http://10.0.0.2:8080\n\
+             ssh_username=admin\n\
+             ssh_password=secret\n\
+             ssh_use_agent=true\n\
+             alt_ssh_username=ubnt\n\
+             alt_ssh_password=ubnt\n\
+             alt_ssh_key_path=/home/me/.ssh/id_ed25519\n",
+        );
+        assert_eq!(config.controller_url, "http://10.0.0.2:8080");
+        assert_eq!(config.credentials.len(), 2);
+
+        let default = &config.credentials[0];
+        assert_eq!(default.name, "Default");
+        assert_eq!(default.username, "admin");
+        assert_eq!(default.password, "secret");
+        assert!(default.use_agent);
+        assert_eq!(default.port, None);
+
+        let alternate = &config.credentials[1];
+        assert_eq!(alternate.name, "Alternate");
+        assert_eq!(alternate.username, "ubnt");
+        assert_eq!(alternate.key_path, "/home/me/.ssh/id_ed25519");
+        assert!(!alternate.use_agent);
+    }
+
+    #[test]
+    fn legacy_keys_without_alternate() {
+        let config = parse_config("ssh_username=admin\nssh_password=secret\nalt_ssh_username=\n");
+        assert_eq!(config.credentials.len(), 1);
+        assert_eq!(config.credentials[0].name, "Default");
+    }
+
+    #[test]
+    fn credential_blocks_win_over_legacy_keys() {
+        let config = parse_config(
+            "ssh_username=old\n\
+             \n\
+             [credential]\n\
+             name=Site\n\
+             username=admin\n",
+        );
+        assert_eq!(config.credentials.len(), 1);
+        assert_eq!(config.credentials[0].username, "admin");
+    }
+
+    #[test]
+    fn credential_sets_round_trip() {
+        let config = AppConfig {
+            ssh_port: 2200,
+            credentials: vec![
+                Credentials {
+                    name: String::from("Site A"),
+                    username: String::from("admin"),
+                    password: String::from("p=ss word"),
+                    key_path: String::from("/home/me/.ssh/id_ed25519"),
+                    key_passphrase: String::from("phrase"),
+                    use_agent: true,
+                    port: Some(2222),
+                },
+                Credentials::with_password("Default (ubnt)", "ubnt", "ubnt"),
+            ],
+            ..AppConfig::default()
+        };
+
+        let reloaded = parse_config(&format_config(&config));
+        assert_eq!(reloaded.ssh_port, 2200);
+        assert_eq!(reloaded.credentials.len(), 2);
+
+        let site = &reloaded.credentials[0];
+        assert_eq!(site.name, "Site A");
+        assert_eq!(site.username, "admin");
+        assert_eq!(site.password, "p=ss word");
+        assert_eq!(site.key_path, "/home/me/.ssh/id_ed25519");
+        assert_eq!(site.key_passphrase, "phrase");
+        assert!(site.use_agent);
+        assert_eq!(site.port, Some(2222));
+
+        // Saved as an empty `port=`, which reads back as no port
+        let default = &reloaded.credentials[1];
+        assert_eq!(default.username, "ubnt");
+        assert_eq!(default.port, None);
+        assert!(format_config(&config).contains("\nport=\n"));
+    }
+
+    #[test]
+    fn bad_credential_port_is_ignored() {
+        let config = parse_config("[credential]\nname=A\nusername=a\nport=0\n[credential]\nname=B\nusername=b\nport=ssh\n");
+        assert_eq!(config.credentials.len(), 2);
+        assert_eq!(config.credentials[0].port, None);
+        assert_eq!(config.credentials[1].port, None);
+    }
 }
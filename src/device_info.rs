@@ -0,0 +1,163 @@
+/// What a UniFi device reports about itself via `info` / `mca-cli-op info`.
+///
+/// ```text
+/// Model:       UAP-AC-Lite
+/// Version:     6.5.28.14491
+/// MAC Address: 78:8a:20:aa:bb:cc
+/// IP Address:  192.168.1.20
+/// Hostname:    UAP-AC-Lite
+/// Status:      Connected (http://192.168.1.1:8080/inform)
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeviceInfo {
+    pub model: String,
+    pub version: String,
+    pub mac: String,
+    pub ip: String,
+    pub hostname: String,
+    /// Raw status line, e.g. `Not Adopted` or `Connected (http://.../inform)`.
+    pub status: String,
+}
+
+impl DeviceInfo {
+    /// The status without the inform URL, e.g. `Connected`.
+    pub fn inform_state(&self) -> &str {
+        match self.status.find(" (") {
+            Some(pos) => &self.status[..pos],
+            None => &self.status,
+        }
+    }
+
+    /// The inform URL the device is currently using, if it reports one.
+    pub fn inform_url(&self) -> Option<&str> {
+        let start = self.status.find('(')? + 1;
+        let end = self.status[start..].find(')')? + start;
+        Some(self.status[start..end].trim())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.inform_state().eq_ignore_ascii_case("Connected")
+    }
+
+    /// True once the device has reached `inform_url` after `set-inform`:
+    /// either connected, or being adopted by the controller.
+    pub fn has_reached(&self, inform_url: &str) -> bool {
+        let state = self.inform_state();
+        (state.eq_ignore_ascii_case("Connected") || state.eq_ignore_ascii_case("Adopting"))
+            && self.inform_url_is(inform_url)
+    }
+
+    /// True when the device is already connected to `inform_url`, so sending
+    /// `set-inform` again would be pointless.
+    pub fn is_connected_to(&self, inform_url: &str) -> bool {
+        self.is_connected() && self.inform_url_is(inform_url)
+    }
+
+    fn inform_url_is(&self, inform_url: &str) -> bool {
+        self.inform_url()
+            .is_some_and(|url| url.trim_end_matches('/') == inform_url.trim_end_matches('/'))
+    }
+}
+
+/// Parse the output of `info`. Returns `None` when no model line is found
+/// (command missing, or the shell printed something else).
+pub fn parse_info(output: &str) -> Option<DeviceInfo> {
+    let mut info = DeviceInfo::default();
+
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "Model" => info.model = value,
+            "Version" => info.version = value,
+            "MAC Address" | "MAC" => info.mac = value.to_uppercase(),
+            "IP Address" | "IP" => info.ip = value,
+            "Hostname" => info.hostname = value,
+            "Status" => info.status = value,
+            _ => {}
+        }
+    }
+
+    if info.model.is_empty() {
+        None
+    } else {
+        Some(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOT_ADOPTED: &str = "UBNT-BZ.v6.5.28# info
+
+Model:       UAP-AC-Lite
+Version:     6.5.28.14491
+MAC Address: 78:8a:20:aa:bb:cc
+IP Address:  192.168.1.20
+Hostname:    UAP-AC-Lite
+Uptime:      5120 seconds
+
+Status:      Not Adopted (http://unifi:8080/inform)
+
+UBNT-BZ.v6.5.28# ";
+
+    const CONNECTED: &str = "USW-Lite-8-PoE-US.6.5.59# mca-cli-op info
+
+Model:       USW-Lite-8-PoE
+Version:     6.5.59.14777
+MAC Address: 74:ac:b9:11:22:33
+IP Address:  192.168.1.21
+Hostname:    USW-Lite-8-PoE
+Uptime:      86400 seconds
+
+Status:      Connected (http://192.168.1.1:8080/inform)
+";
+
+    #[test]
+    fn not_adopted() {
+        let info = parse_info(NOT_ADOPTED).unwrap();
+        assert_eq!(info.model, "UAP-AC-Lite");
+        assert_eq!(info.version, "6.5.28.14491");
+        assert_eq!(info.ip, "192.168.1.20");
+        assert_eq!(info.inform_state(), "Not Adopted");
+        assert_eq!(info.inform_url(), Some("http://unifi:8080/inform"));
+        assert!(!info.is_connected());
+        assert!(!info.has_reached("http://unifi:8080/inform"));
+    }
+
+    #[test]
+    fn connected() {
+        let info = parse_info(CONNECTED).unwrap();
+        assert_eq!(info.model, "USW-Lite-8-PoE");
+        assert_eq!(info.inform_state(), "Connected");
+        assert_eq!(info.inform_url(), Some("http://192.168.1.1:8080/inform"));
+        assert!(info.is_connected());
+        assert!(info.has_reached("http://192.168.1.1:8080/inform"));
+    }
+
+    #[test]
+    fn inform_url_comparison() {
+        let info = parse_info(CONNECTED).unwrap();
+        assert!(info.is_connected_to("http://192.168.1.1:8080/inform"));
+        assert!(info.is_connected_to("http://192.168.1.1:8080/inform/"));
+        assert!(!info.is_connected_to("http://192.168.1.2:8080/inform"));
+
+        let trailing = parse_info(&CONNECTED.replace("/inform)", "/inform/)")).unwrap();
+        assert!(trailing.is_connected_to("http://192.168.1.1:8080/inform"));
+    }
+
+    #[test]
+    fn mac_keeps_its_colons() {
+        let info = parse_info(NOT_ADOPTED).unwrap();
+        assert_eq!(info.mac, "78:8A:20:AA:BB:CC");
+    }
+
+    #[test]
+    fn no_model() {
+        assert_eq!(parse_info("-sh: info: not found\n"), None);
+        assert_eq!(parse_info(""), None);
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// Hostnames from a DHCP server's lease file, by MAC and by address.
+#[derive(Debug, Clone, Default)]
+pub struct Leases {
+    by_mac: HashMap<String, String>,
+    by_ip: HashMap<IpAddr, String>,
+}
+
+impl Leases {
+    /// Read a dnsmasq or ISC dhcpd lease file. The format is detected from
+    /// the contents.
+    pub async fn load(path: &Path) -> Result<Self, String> {
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| format!("Can't read {}: {}", path.display(), e))?;
+        Ok(Self::parse(&contents))
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut leases = Self::default();
+        let entries = if contents.contains("lease ") && contents.contains('{') {
+            parse_isc(contents)
+        } else {
+            parse_dnsmasq(contents)
+        };
+        // Later leases are newer, so they win
+        for (ip, mac, hostname) in entries {
+            if let Some(mac) = mac {
+                leases.by_mac.insert(mac, hostname.clone());
+            }
+            leases.by_ip.insert(ip, hostname);
+        }
+        leases
+    }
+
+    /// The hostname leased to `mac`, or failing that to `ip`.
+    pub fn hostname(&self, mac: &str, ip: &str) -> Option<&str> {
+        let ip = ip.split('%').next().unwrap_or_default().parse().ok();
+        self.by_mac
+            .get(&mac.to_uppercase())
+            .or_else(|| ip.and_then(|ip| self.by_ip.get(&ip)))
+            .map(String::as_str)
+    }
+}
+
+type Lease = (IpAddr, Option<String>, String);
+
+// dnsmasq, one lease per line (`*` for no hostname):
+// 1700000000 78:8a:20:aa:bb:cc 192.168.1.20 UAP-Lobby 01:78:8a:20:aa:bb:cc
+fn parse_dnsmasq(contents: &str) -> Vec<Lease> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 4 || parts[3] == "*" {
+                return None;
+            }
+            let ip = parts[2].parse().ok()?;
+            Some((ip, Some(parts[1].to_uppercase()), parts[3].to_string()))
+        })
+        .collect()
+}
+
+// ISC dhcpd.leases, one block per lease:
+// lease 192.168.1.20 {
+//   hardware ethernet 78:8a:20:aa:bb:cc;
+//   client-hostname "UAP-Lobby";
+// }
+fn parse_isc(contents: &str) -> Vec<Lease> {
+    let mut leases = Vec::new();
+    let mut current: Option<(IpAddr, Option<String>, Option<String>)> = None;
+    for line in contents.lines() {
+        let line = line.trim().trim_end_matches(';');
+        if let Some(rest) = line.strip_prefix("lease ") {
+            current = rest
+                .trim_end_matches('{')
+                .trim()
+                .parse()
+                .ok()
+                .map(|ip| (ip, None, None));
+        } else if line == "}" {
+            if let Some((ip, mac, Some(hostname))) = current.take() {
+                leases.push((ip, mac, hostname));
+            }
+        } else if let Some((_, mac, hostname)) = current.as_mut() {
+            if let Some(value) = line.strip_prefix("hardware ethernet ") {
+                *mac = Some(value.trim().to_uppercase());
+            } else if let Some(value) = line.strip_prefix("client-hostname ") {
+                *hostname = Some(value.trim().trim_matches('"').to_string());
+            }
+        }
+    }
+    leases
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dnsmasq() {
+        let leases = Leases::parse(
+            "1700000000 78:8a:20:aa:bb:cc 192.168.1.20 UAP-Lobby 01:78:8a:20:aa:bb:cc\n\
+             1700000100 f0:9f:c2:11:22:33 192.168.1.21 * *\n",
+        );
+        assert_eq!(leases.hostname("78:8A:20:AA:BB:CC", "10.0.0.1"), Some("UAP-Lobby"));
+        assert_eq!(leases.hostname("Unknown", "192.168.1.20"), Some("UAP-Lobby"));
+        assert_eq!(leases.hostname("F0:9F:C2:11:22:33", "192.168.1.21"), None);
+    }
+
+    #[test]
+    fn isc_keeps_newest_lease() {
+        let leases = Leases::parse(
+            r#"# The format of this file is documented in the dhcpd.leases(5) manual page.
+lease 192.168.1.20 {
+  starts 4 2024/01/04 10:00:00;
+  hardware ethernet 78:8a:20:aa:bb:cc;
+  client-hostname "UAP-Old";
+}
+lease 192.168.1.30 {
+  hardware ethernet 78:8a:20:aa:bb:cc;
+  client-hostname "UAP-Lobby";
+}
+lease 192.168.1.31 {
+  hardware ethernet f0:9f:c2:11:22:33;
+}
+"#,
+        );
+        assert_eq!(leases.hostname("78:8a:20:aa:bb:cc", "192.168.1.30"), Some("UAP-Lobby"));
+        assert_eq!(leases.hostname("Unknown", "192.168.1.20"), Some("UAP-Old"));
+        assert_eq!(leases.hostname("F0:9F:C2:11:22:33", "192.168.1.31"), None);
+    }
+}
@@ -0,0 +1,337 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddrV4};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// UDP port UniFi devices answer discovery probes on.
+pub const DISCOVERY_PORT: u16 = 10001;
+
+// Version 1 discovery request: version, command, zero-length payload
+const PROBE: [u8; 4] = [0x01, 0x00, 0x00, 0x00];
+
+// TLV types in discovery replies
+const TLV_HWADDR: u8 = 0x01;
+const TLV_IPINFO: u8 = 0x02;
+const TLV_FIRMWARE: u8 = 0x03;
+const TLV_UPTIME: u8 = 0x0A;
+const TLV_HOSTNAME: u8 = 0x0B;
+const TLV_PLATFORM: u8 = 0x0C;
+const TLV_ESSID: u8 = 0x0D;
+const TLV_MODEL: u8 = 0x14;
+const TLV_MODEL_V2: u8 = 0x15;
+const TLV_VERSION: u8 = 0x16;
+const TLV_DEFAULT: u8 = 0x17;
+
+/// What a UniFi device reports about itself in reply to a discovery probe.
+/// Empty strings mean the reply didn't include that field.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiscoveryInfo {
+    /// Uppercase `AA:BB:CC:DD:EE:FF`.
+    pub mac: String,
+    pub ip: Option<Ipv4Addr>,
+    /// Full model name, e.g. `UAP-AC-Lite`.
+    pub model: String,
+    /// Short platform code, e.g. `U7LT`.
+    pub platform: String,
+    /// Full firmware string, e.g. `BZ.qca956x.v6.5.28.14491.230320.1636`.
+    pub firmware: String,
+    /// Short firmware version, e.g. `6.5.28`.
+    pub version: String,
+    pub hostname: String,
+    pub essid: String,
+    pub uptime: Option<Duration>,
+    /// True while the device is in factory default state (not adopted).
+    pub is_default: Option<bool>,
+}
+
+impl DiscoveryInfo {
+    /// The version if reported, otherwise the full firmware string.
+    pub fn display_version(&self) -> &str {
+        if self.version.is_empty() {
+            &self.firmware
+        } else {
+            &self.version
+        }
+    }
+}
+
+/// Decode a discovery reply. Returns `None` for anything that isn't a
+/// well-formed reply, including the probes themselves.
+pub fn parse_response(packet: &[u8]) -> Option<DiscoveryInfo> {
+    let (&version, rest) = packet.split_first()?;
+    if !(version == 1 || version == 2) || rest.len() < 3 {
+        return None;
+    }
+    let length = u16::from_be_bytes([rest[1], rest[2]]) as usize;
+    let mut payload = rest.get(3..3 + length)?;
+    if payload.is_empty() {
+        return None;
+    }
+
+    let mut info = DiscoveryInfo::default();
+    while !payload.is_empty() {
+        if payload.len() < 3 {
+            return None;
+        }
+        let kind = payload[0];
+        let len = u16::from_be_bytes([payload[1], payload[2]]) as usize;
+        let value = payload.get(3..3 + len)?;
+        payload = &payload[3 + len..];
+
+        let text = || String::from_utf8_lossy(value).trim_end_matches('\0').to_string();
+        match kind {
+            TLV_HWADDR if len == 6 => info.mac = format_mac(value),
+            TLV_IPINFO if len == 10 => {
+                if info.mac.is_empty() {
+                    info.mac = format_mac(&value[..6]);
+                }
+                // Devices list one ipinfo per interface; keep the first
+                if info.ip.is_none() {
+                    info.ip = Some(Ipv4Addr::new(value[6], value[7], value[8], value[9]));
+                }
+            }
+            TLV_FIRMWARE => info.firmware = text(),
+            TLV_UPTIME if len == 4 => {
+                let secs = u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+                info.uptime = Some(Duration::from_secs(secs as u64));
+            }
+            TLV_HOSTNAME => info.hostname = text(),
+            TLV_PLATFORM => info.platform = text(),
+            TLV_ESSID => info.essid = text(),
+            // Some firmware sends both; the full name is the one to show
+            TLV_MODEL => info.model = text(),
+            TLV_MODEL_V2 if info.model.is_empty() => info.model = text(),
+            TLV_VERSION => info.version = text(),
+            TLV_DEFAULT if len == 1 => info.is_default = Some(value[0] != 0),
+            _ => {}
+        }
+    }
+
+    if info.mac.is_empty() && info.model.is_empty() && info.platform.is_empty() {
+        None
+    } else {
+        Some(info)
+    }
+}
+
+fn format_mac(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Send a discovery probe straight to `ip`, from `source` if given, and wait
+/// up to `timeout` for it to answer.
+pub async fn probe(ip: Ipv4Addr, source: Option<Ipv4Addr>, timeout: Duration) -> Option<DiscoveryInfo> {
+    let socket = UdpSocket::bind((source.unwrap_or(Ipv4Addr::UNSPECIFIED), 0)).await.ok()?;
+    socket.send_to(&PROBE, (ip, DISCOVERY_PORT)).await.ok()?;
+
+    let mut buf = [0u8; 1500];
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let (len, from) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf))
+            .await
+            .ok()?
+            .ok()?;
+        if from.ip() == ip {
+            if let Some(info) = parse_response(&buf[..len]) {
+                return Some(info);
+            }
+        }
+    }
+}
+
+/// Broadcast a discovery probe to `broadcast` (e.g. the subnet broadcast
+/// address or 255.255.255.255), from `source` if given, and collect every
+/// reply that arrives within `listen`, keyed by the address it came from.
+pub async fn broadcast(
+    broadcast: Ipv4Addr,
+    source: Option<Ipv4Addr>,
+    listen: Duration,
+) -> HashMap<Ipv4Addr, DiscoveryInfo> {
+    let mut found = HashMap::new();
+    let Ok(socket) = UdpSocket::bind((source.unwrap_or(Ipv4Addr::UNSPECIFIED), 0)).await else {
+        return found;
+    };
+    if socket.set_broadcast(true).is_err()
+        || socket.send_to(&PROBE, (broadcast, DISCOVERY_PORT)).await.is_err()
+    {
+        return found;
+    }
+
+    let mut buf = [0u8; 1500];
+    let deadline = tokio::time::Instant::now() + listen;
+    while let Ok(Ok((len, from))) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        if let (std::net::IpAddr::V4(ip), Some(info)) = (from.ip(), parse_response(&buf[..len])) {
+            found.insert(ip, info);
+        }
+    }
+    found
+}
+
+/// Socket bound to the discovery port that receives the announcements
+/// unadopted devices broadcast every few seconds.
+pub struct Listener {
+    socket: UdpSocket,
+}
+
+impl Listener {
+    /// Bind UDP 10001 on all interfaces. The port is shared with anything
+    /// else that allows it (a local controller, say); otherwise this fails
+    /// if it's in use.
+    pub fn bind() -> io::Result<Self> {
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT).into())?;
+        Ok(Self {
+            socket: UdpSocket::from_std(socket.into())?,
+        })
+    }
+
+    /// Wait for the next well-formed announcement and the address it came
+    /// from. Probes and anything else on the port are skipped.
+    pub async fn next(&self) -> io::Result<(Ipv4Addr, DiscoveryInfo)> {
+        let mut buf = [0u8; 1500];
+        loop {
+            let (len, from) = self.socket.recv_from(&mut buf).await?;
+            if let (std::net::IpAddr::V4(ip), Some(info)) = (from.ip(), parse_response(&buf[..len])) {
+                return Ok((ip, info));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Replies as tcpdump shows their UDP payload (`tcpdump -X udp port 10001`)
+    fn hex(dump: &str) -> Vec<u8> {
+        dump.split_whitespace()
+            .map(|byte| u8::from_str_radix(byte, 16).unwrap())
+            .collect()
+    }
+
+    // Version 1 reply from a factory-default UAP-AC-Lite on 6.5.28
+    const FACTORY_DEFAULT_AP: &str = "\
+        01 00 00 98 02 00 0a 78 8a 20 aa bb cc c0 a8 01
+        14 01 00 06 78 8a 20 aa bb cc 0a 00 04 00 00 01
+        9c 0b 00 0b 55 41 50 2d 41 43 2d 4c 69 74 65 0c
+        00 04 55 37 4c 54 03 00 24 42 5a 2e 71 63 61 39
+        35 36 78 2e 76 36 2e 35 2e 32 38 2e 31 34 34 39
+        31 2e 32 33 30 33 32 30 2e 31 36 33 36 16 00 06
+        36 2e 35 2e 32 38 15 00 0b 55 41 50 2d 41 43 2d
+        4c 69 74 65 17 00 01 01 18 00 01 00 19 00 01 01
+        1a 00 01 01 12 00 04 00 00 00 01 13 00 06 78 8a
+        20 aa bb cc 1b 00 05 34 2e 30 2e 30
+    ";
+
+    // Version 1 reply from an adopted US-8-60W with two addresses
+    const ADOPTED_SWITCH: &str = "\
+        01 00 00 9d 02 00 0a f0 9f c2 11 22 33 0a 00 08
+        0e 02 00 0a f0 9f c2 11 22 33 a9 fe 22 33 01 00
+        06 f0 9f c2 11 22 33 0a 00 04 00 1e 52 8c 0b 00
+        0e 73 77 2d 6f 66 66 69 63 65 2d 72 61 63 6b 0c
+        00 06 55 53 38 50 36 30 03 00 25 55 53 2e 62 63
+        6d 35 33 33 34 78 2e 76 36 2e 35 2e 35 39 2e 31
+        34 37 37 37 2e 32 33 30 34 31 33 2e 30 39 33 38
+        16 00 06 36 2e 35 2e 35 39 15 00 06 55 53 38 50
+        36 30 14 00 08 55 53 2d 38 2d 36 30 57 17 00 01
+        00 12 00 04 00 00 00 03 13 00 06 f0 9f c2 11 22
+        33
+    ";
+
+    // Version 2 reply from an adopted U6-Lite on 6.6.55
+    const V2_ACCESS_POINT: &str = "\
+        02 06 00 98 01 00 06 74 ac b9 44 55 66 02 00 0a
+        74 ac b9 44 55 66 0a 00 08 1f 0a 00 04 00 01 51
+        80 0b 00 08 61 70 2d 6c 6f 62 62 79 0c 00 04 55
+        41 4c 36 03 00 25 55 41 4c 36 2e 6d 74 37 36 32
+        31 2e 76 36 2e 36 2e 35 35 2e 31 35 31 38 39 2e
+        32 33 31 31 32 34 2e 31 36 33 32 16 00 06 36 2e
+        36 2e 35 35 14 00 07 55 36 2d 4c 69 74 65 15 00
+        04 55 41 4c 36 0d 00 00 17 00 01 00 18 00 01 00
+        1c 00 02 00 16 26 00 14 61 70 2d 6c 6f 62 62 79
+        2e 65 78 61 6d 70 6c 65 2e 6c 61 6e
+    ";
+
+    #[test]
+    fn factory_default_access_point() {
+        let info = parse_response(&hex(FACTORY_DEFAULT_AP)).unwrap();
+        assert_eq!(info.mac, "78:8A:20:AA:BB:CC");
+        assert_eq!(info.ip, Some(Ipv4Addr::new(192, 168, 1, 20)));
+        assert_eq!(info.model, "UAP-AC-Lite");
+        assert_eq!(info.platform, "U7LT");
+        assert_eq!(info.hostname, "UAP-AC-Lite");
+        assert_eq!(info.firmware, "BZ.qca956x.v6.5.28.14491.230320.1636");
+        assert_eq!(info.version, "6.5.28");
+        assert_eq!(info.display_version(), "6.5.28");
+        assert_eq!(info.uptime, Some(Duration::from_secs(412)));
+        assert_eq!(info.is_default, Some(true));
+        assert_eq!(info.essid, "");
+    }
+
+    #[test]
+    fn adopted_switch() {
+        let info = parse_response(&hex(ADOPTED_SWITCH)).unwrap();
+        assert_eq!(info.mac, "F0:9F:C2:11:22:33");
+        // The first interface's address, not the link-local fallback
+        assert_eq!(info.ip, Some(Ipv4Addr::new(10, 0, 8, 14)));
+        // The full model name wins over the short one listed before it
+        assert_eq!(info.model, "US-8-60W");
+        assert_eq!(info.platform, "US8P60");
+        assert_eq!(info.hostname, "sw-office-rack");
+        assert_eq!(info.firmware, "US.bcm5334x.v6.5.59.14777.230413.0938");
+        assert_eq!(info.display_version(), "6.5.59");
+        assert_eq!(info.uptime, Some(Duration::from_secs(1_987_212)));
+        assert_eq!(info.is_default, Some(false));
+    }
+
+    #[test]
+    fn version_2_reply() {
+        let info = parse_response(&hex(V2_ACCESS_POINT)).unwrap();
+        assert_eq!(info.mac, "74:AC:B9:44:55:66");
+        assert_eq!(info.ip, Some(Ipv4Addr::new(10, 0, 8, 31)));
+        assert_eq!(info.model, "U6-Lite");
+        assert_eq!(info.platform, "UAL6");
+        assert_eq!(info.hostname, "ap-lobby");
+        assert_eq!(info.firmware, "UAL6.mt7621.v6.6.55.15189.231124.1632");
+        assert_eq!(info.version, "6.6.55");
+        assert_eq!(info.uptime, Some(Duration::from_secs(86_400)));
+        assert_eq!(info.is_default, Some(false));
+    }
+
+    #[test]
+    fn firmware_without_version() {
+        // Older firmware leaves out the short version
+        let mut packet = hex(ADOPTED_SWITCH);
+        let at = packet.windows(3).position(|w| w == [TLV_VERSION, 0x00, 0x06]).unwrap();
+        packet[at] = 0x7F;
+        let info = parse_response(&packet).unwrap();
+        assert_eq!(info.version, "");
+        assert_eq!(info.display_version(), "US.bcm5334x.v6.5.59.14777.230413.0938");
+    }
+
+    #[test]
+    fn rejects_probe_and_garbage() {
+        assert_eq!(parse_response(&PROBE), None);
+        assert_eq!(parse_response(&[]), None);
+        assert_eq!(parse_response(b"SSH-2.0-dropbear"), None);
+    }
+
+    #[test]
+    fn rejects_truncated_tlv() {
+        // Header claims more payload than was received
+        assert_eq!(parse_response(&hex(FACTORY_DEFAULT_AP)[..40]), None);
+
+        // TLV length runs past the end of the payload
+        let packet = [0x01, 0x00, 0x00, 0x05, 0x0B, 0x00, 0x09, b'a', b'b'];
+        assert_eq!(parse_response(&packet), None);
+    }
+}
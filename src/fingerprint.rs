@@ -0,0 +1,200 @@
+use crate::rate_limit::RateLimiter;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Ports probed when the config doesn't say otherwise. 10001 stands for the
+/// UniFi discovery probe (UDP) rather than a TCP connect.
+pub const DEFAULT_PORTS: &[u16] = &[22, 80, 443, 8080, 8443, 10001];
+
+// Timeout for the TCP handshake on each port
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+// How long to wait for a server that speaks first (SSH), and then for the
+// answer to our HTTP request
+const BANNER_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Most of a reply that's read looking for a banner
+const MAX_BANNER_READ: usize = 4096;
+
+/// A TCP port that accepted a connection during the scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenPort {
+    pub port: u16,
+    /// The SSH identification line (`SSH-2.0-dropbear_2019.78`) or the HTTP
+    /// `Server` header (`lighttpd/1.4.39`), when the service gave one.
+    pub banner: Option<String>,
+}
+
+impl OpenPort {
+    /// The port speaks SSH: it sent an SSH identification line, or it is 22
+    /// and sent nothing to say otherwise.
+    pub fn is_ssh(&self) -> bool {
+        match &self.banner {
+            Some(banner) => banner.starts_with("SSH-"),
+            None => self.port == 22,
+        }
+    }
+
+    /// The port answered an HTTP request (plain, or as the error a TLS
+    /// server sends to plain HTTP).
+    pub fn is_http(&self) -> bool {
+        self.banner.as_ref().is_some_and(|banner| !banner.starts_with("SSH-"))
+    }
+}
+
+impl std::fmt::Display for OpenPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.banner {
+            Some(banner) => write!(f, "{} ({})", self.port, banner),
+            None => write!(f, "{}", self.port),
+        }
+    }
+}
+
+/// Parse a comma-separated port list, e.g. `22, 80, 443`.
+pub fn parse_ports(spec: &str) -> Result<Vec<u16>, String> {
+    let mut ports = Vec::new();
+    for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        match item.parse::<u16>() {
+            Ok(port) if port > 0 => ports.push(port),
+            _ => return Err(format!("Invalid port: {}", item)),
+        }
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    Ok(ports)
+}
+
+/// Format a port list the way [`parse_ports`] reads it.
+pub fn format_ports(ports: &[u16]) -> String {
+    ports.iter().map(u16::to_string).collect::<Vec<_>>().join(",")
+}
+
+/// Try each of `ports` on `ip` at once and return the ones that accept a
+/// connection, in port order, with whatever banner they give. Connections
+/// go out from `source` when given, each in its own `limiter` slot. Port
+/// 10001 is skipped; it's UDP.
+pub async fn probe_ports(
+    ip: SocketAddr,
+    ports: &[u16],
+    source: Option<Ipv4Addr>,
+    limiter: &RateLimiter,
+) -> Vec<OpenPort> {
+    let mut probes = tokio::task::JoinSet::new();
+    for &port in ports.iter().filter(|&&port| port != crate::discovery::DISCOVERY_PORT) {
+        let mut addr = ip;
+        addr.set_port(port);
+        let limiter = limiter.clone();
+        probes.spawn(async move {
+            limiter.wait().await;
+            let stream = connect(addr, source).await?;
+            let banner = grab_banner(stream, addr.ip()).await;
+            Some(OpenPort { port, banner })
+        });
+    }
+
+    let mut open = Vec::new();
+    while let Some(result) = probes.join_next().await {
+        open.extend(result.ok().flatten());
+    }
+    open.sort_by_key(|p| p.port);
+    open
+}
+
+async fn connect(addr: SocketAddr, source: Option<Ipv4Addr>) -> Option<TcpStream> {
+    let connect = async {
+        let socket = match addr {
+            SocketAddr::V4(_) => tokio::net::TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => tokio::net::TcpSocket::new_v6()?,
+        };
+        if let (Some(source), SocketAddr::V4(_)) = (source, addr) {
+            socket.bind((source, 0).into())?;
+        }
+        socket.connect(addr).await
+    };
+    tokio::time::timeout(CONNECT_TIMEOUT, connect).await.ok()?.ok()
+}
+
+// Wait for a server-first banner; if none comes, ask for one over HTTP
+async fn grab_banner(mut stream: TcpStream, ip: IpAddr) -> Option<String> {
+    let mut buf = vec![0u8; MAX_BANNER_READ];
+    if let Ok(Ok(n)) = tokio::time::timeout(BANNER_TIMEOUT, stream.read(&mut buf)).await {
+        return parse_banner(&buf[..n]);
+    }
+
+    let host = match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{}]", ip),
+    };
+    let request = format!("HEAD / HTTP/1.0\r\nHost: {}\r\nUser-Agent: unifi-adopt\r\n\r\n", host);
+    stream.write_all(request.as_bytes()).await.ok()?;
+
+    let read_reply = async {
+        let mut len = 0;
+        while len < buf.len() {
+            match stream.read(&mut buf[len..]).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => len += n,
+            }
+            if buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+                break;
+            }
+        }
+        len
+    };
+    let len = tokio::time::timeout(BANNER_TIMEOUT, read_reply).await.ok()?;
+    parse_banner(&buf[..len])
+}
+
+/// The SSH identification line or HTTP `Server` header at the start of a
+/// service's reply.
+pub fn parse_banner(reply: &[u8]) -> Option<String> {
+    let reply = String::from_utf8_lossy(reply);
+    let mut lines = reply.lines();
+    let first = lines.next()?.trim();
+    if first.starts_with("SSH-") {
+        return Some(first.to_string());
+    }
+    if !first.starts_with("HTTP/") {
+        return None;
+    }
+    lines
+        .take_while(|line| !line.trim().is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("server"))
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ssh_banner() {
+        let banner = parse_banner(b"SSH-2.0-dropbear_2019.78\r\n");
+        assert_eq!(banner.as_deref(), Some("SSH-2.0-dropbear_2019.78"));
+        let port = OpenPort { port: 2222, banner };
+        assert!(port.is_ssh());
+        assert!(!port.is_http());
+    }
+
+    #[test]
+    fn http_server_header() {
+        let reply = b"HTTP/1.1 400 Bad Request\r\nserver: nginx\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(parse_banner(reply).as_deref(), Some("nginx"));
+        assert_eq!(parse_banner(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"), None);
+        assert_eq!(parse_banner(b"\x15\x03\x01\x00\x02\x02\x46"), None);
+    }
+
+    #[test]
+    fn port_lists() {
+        assert_eq!(parse_ports("443, 22,22,8443").unwrap(), vec![22, 443, 8443]);
+        assert_eq!(parse_ports("").unwrap(), Vec::<u16>::new());
+        assert!(parse_ports("22,ssh").is_err());
+        assert!(parse_ports("0").is_err());
+        assert_eq!(format_ports(DEFAULT_PORTS), "22,80,443,8080,8443,10001");
+    }
+}
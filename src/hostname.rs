@@ -0,0 +1,250 @@
+use crate::rate_limit::RateLimiter;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+// mDNS port; devices answer queries sent straight to it with a unicast reply
+const MDNS_PORT: u16 = 5353;
+
+// DNS record type for reverse lookups
+const TYPE_PTR: u16 = 12;
+
+/// The name `host` goes by, from a reverse DNS (PTR) lookup through the
+/// system resolver or, failing that, from the device's own mDNS responder.
+/// Each lookup waits for a `limiter` slot and gives up after `timeout`.
+///
+/// `host` may carry an IPv6 scope (`fe80::1%eth0`). Trailing dots are
+/// stripped, so mDNS names end in `.local`.
+pub async fn resolve(host: &str, timeout: Duration, limiter: &RateLimiter) -> Option<String> {
+    let addr = crate::network_interface::socket_addr(host, MDNS_PORT).ok()?;
+    limiter.wait().await;
+    let ptr = tokio::time::timeout(timeout, reverse_lookup(addr)).await.ok().flatten();
+    if ptr.is_some() {
+        return ptr;
+    }
+    limiter.wait().await;
+    tokio::time::timeout(timeout, mdns_lookup(addr)).await.ok().flatten()
+}
+
+// PTR lookup through getnameinfo, which also honours /etc/hosts
+#[cfg(unix)]
+async fn reverse_lookup(addr: SocketAddr) -> Option<String> {
+    let name = tokio::task::spawn_blocking(move || {
+        let sockaddr = socket2::SockAddr::from(addr);
+        let mut host = [0 as libc::c_char; 1025];
+        let result = unsafe {
+            libc::getnameinfo(
+                sockaddr.as_ptr().cast(),
+                sockaddr.len(),
+                host.as_mut_ptr(),
+                host.len() as libc::socklen_t,
+                std::ptr::null_mut(),
+                0,
+                libc::NI_NAMEREQD,
+            )
+        };
+        if result != 0 {
+            return None;
+        }
+        let name = unsafe { std::ffi::CStr::from_ptr(host.as_ptr()) };
+        Some(name.to_string_lossy().to_string())
+    })
+    .await
+    .ok()??;
+    clean_name(&name)
+}
+
+// PTR lookup through nslookup, which prints the answer as `Name: host`
+#[cfg(not(unix))]
+async fn reverse_lookup(addr: SocketAddr) -> Option<String> {
+    let mut cmd = tokio::process::Command::new("nslookup");
+    cmd.arg(addr.ip().to_string()).kill_on_drop(true);
+
+    // Hide console window on Windows
+    #[cfg(target_os = "windows")]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let output = cmd.output().await.ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("Name:"))
+        .find_map(clean_name)
+}
+
+// Ask the device's mDNS responder for its own reverse name
+async fn mdns_lookup(addr: SocketAddr) -> Option<String> {
+    let bind: SocketAddr = match addr {
+        SocketAddr::V4(_) => (std::net::Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (std::net::Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = tokio::net::UdpSocket::bind(bind).await.ok()?;
+    let id = std::process::id() as u16 ^ addr.port();
+    let query = build_query(id, &reverse_name(addr.ip()), TYPE_PTR);
+    socket.send_to(&query, addr).await.ok()?;
+
+    let mut buf = [0u8; 1500];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await.ok()?;
+        if from.ip() == addr.ip() {
+            if let Some(name) = parse_ptr_answer(&buf[..len], id) {
+                return clean_name(&name);
+            }
+        }
+    }
+}
+
+// An address that's just the IP spelled back isn't a name
+fn clean_name(name: &str) -> Option<String> {
+    let name = name.trim().trim_end_matches('.');
+    if name.is_empty() || name.parse::<IpAddr>().is_ok() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// The name a reverse lookup of `ip` asks for, e.g. `20.1.168.192.in-addr.arpa`.
+pub fn reverse_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, d] = ip.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", d, c, b, a)
+        }
+        IpAddr::V6(ip) => {
+            let mut name = String::new();
+            for byte in ip.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", byte & 0x0f, byte >> 4));
+            }
+            name + "ip6.arpa"
+        }
+    }
+}
+
+// A DNS query with one question for `name`
+fn build_query(id: u16, name: &str, qtype: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(64);
+    packet.extend_from_slice(&id.to_be_bytes());
+    // Flags (standard query), one question, no other records
+    packet.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    // Class IN
+    packet.extend_from_slice(&1u16.to_be_bytes());
+    packet
+}
+
+/// The target of the first PTR record in the answer section of a DNS reply
+/// with the given `id`.
+pub fn parse_ptr_answer(packet: &[u8], id: u16) -> Option<String> {
+    if packet.len() < 12 || u16::from_be_bytes([packet[0], packet[1]]) != id {
+        return None;
+    }
+    // Must be a response
+    if packet[2] & 0x80 == 0 {
+        return None;
+    }
+    let questions = u16::from_be_bytes([packet[4], packet[5]]);
+    let answers = u16::from_be_bytes([packet[6], packet[7]]);
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(packet, pos)? + 4;
+    }
+    for _ in 0..answers {
+        pos = skip_name(packet, pos)?;
+        let fixed = packet.get(pos..pos + 10)?;
+        let rtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+        let rdlength = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        pos += 10;
+        if rtype == TYPE_PTR {
+            return read_name(packet, pos);
+        }
+        pos += rdlength;
+    }
+    None
+}
+
+// Offset just past the (possibly compressed) name at `pos`
+fn skip_name(packet: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(pos)? as usize;
+        if len == 0 {
+            return Some(pos + 1);
+        }
+        if len & 0xc0 == 0xc0 {
+            return Some(pos + 2);
+        }
+        pos += 1 + len;
+    }
+}
+
+// The dotted name at `pos`, following compression pointers
+fn read_name(packet: &[u8], mut pos: usize) -> Option<String> {
+    let mut labels = Vec::new();
+    // Pointers must go backwards, so a loop can't go on forever, but cap it
+    // anyway
+    for _ in 0..128 {
+        let len = *packet.get(pos)? as usize;
+        if len == 0 {
+            return Some(labels.join("."));
+        }
+        if len & 0xc0 == 0xc0 {
+            let target = ((len & 0x3f) << 8) | *packet.get(pos + 1)? as usize;
+            if target >= pos {
+                return None;
+            }
+            pos = target;
+            continue;
+        }
+        let label = packet.get(pos + 1..pos + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        pos += 1 + len;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverse_names() {
+        assert_eq!(reverse_name("192.168.1.20".parse().unwrap()), "20.1.168.192.in-addr.arpa");
+        let v6 = reverse_name("2001:db8::1".parse().unwrap());
+        assert!(v6.starts_with("1.0.0.0.0.0.0.0."));
+        assert!(v6.ends_with("8.b.d.0.1.0.0.2.ip6.arpa"));
+    }
+
+    #[test]
+    fn ptr_answer_with_compression() {
+        let mut reply = build_query(0x1234, "20.1.168.192.in-addr.arpa", TYPE_PTR);
+        // Response, one answer
+        reply[2] = 0x84;
+        reply[7] = 1;
+        // Name: pointer to the question at offset 12
+        reply.extend_from_slice(&[0xc0, 12]);
+        reply.extend_from_slice(&TYPE_PTR.to_be_bytes());
+        reply.extend_from_slice(&[0, 1, 0, 0, 0, 120]);
+        let rdata = b"\x09UAP-Lobby\x05local\x00";
+        reply.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        reply.extend_from_slice(rdata);
+
+        assert_eq!(parse_ptr_answer(&reply, 0x1234).as_deref(), Some("UAP-Lobby.local"));
+        assert_eq!(parse_ptr_answer(&reply, 0x4321), None);
+        assert_eq!(parse_ptr_answer(&reply[..reply.len() - 4], 0x1234), None);
+    }
+
+    #[test]
+    fn query_is_not_an_answer() {
+        let query = build_query(7, "20.1.168.192.in-addr.arpa", TYPE_PTR);
+        assert_eq!(parse_ptr_answer(&query, 7), None);
+    }
+}
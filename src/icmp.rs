@@ -0,0 +1,220 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6, UdpSocket};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+const ECHO_REQUEST: u8 = 8;
+const ECHO_REPLY: u8 = 0;
+const ECHO_REQUEST_V6: u8 = 128;
+const ECHO_REPLY_V6: u8 = 129;
+const PAYLOAD: &[u8] = b"unifi-adopt";
+
+// Every request gets its own sequence number so concurrent probes on raw
+// sockets, which see each other's replies, can tell them apart
+static SEQUENCE: AtomicU16 = AtomicU16::new(0);
+
+/// Send one ICMP echo request to `ip` and wait up to `timeout` for the
+/// reply. Returns the round-trip time, or `None` if nothing answered.
+/// With a `source` address the request is sent from that interface.
+///
+/// Uses an unprivileged datagram ICMP socket where the OS allows it (Linux
+/// with `net.ipv4.ping_group_range`, macOS) and a raw socket otherwise.
+/// Fails when neither can be opened, so the caller can fall back to the
+/// `ping` binary.
+pub fn ping(ip: Ipv4Addr, source: Option<Ipv4Addr>, timeout: Duration) -> io::Result<Option<Duration>> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4))
+        .or_else(|_| Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)))?;
+    if let Some(source) = source {
+        socket.bind(&SocketAddrV4::new(source, 0).into())?;
+    }
+    let socket: UdpSocket = socket.into();
+
+    let identifier = std::process::id() as u16;
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let request = echo_request(identifier, sequence);
+
+    let start = Instant::now();
+    socket.send_to(&request, (ip, 0))?;
+
+    let mut buf = [0u8; 1500];
+    loop {
+        let remaining = match timeout.checked_sub(start.elapsed()) {
+            Some(remaining) if !remaining.is_zero() => remaining,
+            _ => return Ok(None),
+        };
+        socket.set_read_timeout(Some(remaining))?;
+
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+
+        if from.ip() == ip && is_reply_to(&buf[..len], identifier, sequence) {
+            return Ok(Some(start.elapsed()));
+        }
+    }
+}
+
+/// Send one ICMPv6 echo request to the all-nodes group (`ff02::1`) on the
+/// interface with index `interface_index` and collect every host that
+/// replies within `listen`, with its round-trip time.
+///
+/// Hosts answer from their link-local address. This machine's own reply is
+/// left out. Needs the same socket permissions as [`ping`].
+pub fn ping_all_nodes(interface_index: u32, listen: Duration) -> io::Result<HashMap<Ipv6Addr, Duration>> {
+    let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::ICMPV6))
+        .or_else(|_| Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6)))?;
+    socket.set_multicast_if_v6(interface_index)?;
+    let socket: UdpSocket = socket.into();
+
+    let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    // The kernel fills in the checksum for ICMPv6
+    let mut request = echo_request(std::process::id() as u16, sequence);
+    request[0] = ECHO_REQUEST_V6;
+    request[2..4].copy_from_slice(&[0, 0]);
+
+    let all_nodes = SocketAddrV6::new(Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1), 0, 0, interface_index);
+
+    // The address the kernel sends from, which answers too
+    let own = UdpSocket::bind("[::]:0")
+        .and_then(|probe| {
+            probe.connect(all_nodes)?;
+            probe.local_addr()
+        })
+        .map(|addr| addr.ip())
+        .ok();
+
+    let start = Instant::now();
+    socket.send_to(&request, all_nodes)?;
+
+    let mut replies = HashMap::new();
+    let mut buf = [0u8; 1500];
+    while let Some(remaining) = listen.checked_sub(start.elapsed()).filter(|r| !r.is_zero()) {
+        socket.set_read_timeout(Some(remaining))?;
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+            Err(e) => return Err(e),
+        };
+        // ICMPv6 sockets never include the IP header
+        let packet = &buf[..len];
+        if let IpAddr::V6(ip) = from.ip() {
+            if Some(from.ip()) != own && packet.first() == Some(&ECHO_REPLY_V6) && is_echo(packet, sequence) {
+                replies.entry(ip).or_insert_with(|| start.elapsed());
+            }
+        }
+    }
+    Ok(replies)
+}
+
+fn echo_request(identifier: u16, sequence: u16) -> Vec<u8> {
+    let mut packet = vec![ECHO_REQUEST, 0, 0, 0];
+    packet.extend_from_slice(&identifier.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(PAYLOAD);
+
+    let checksum = checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    packet
+}
+
+// Raw sockets (and datagram sockets on macOS) deliver the IPv4 header too;
+// Linux datagram sockets start at the ICMP header. The identifier is only
+// checked with the header, because Linux rewrites it on datagram sockets.
+fn is_reply_to(packet: &[u8], identifier: u16, sequence: u16) -> bool {
+    let (icmp, check_identifier) = match packet.first() {
+        Some(byte) if byte >> 4 == 4 => {
+            let header_len = ((byte & 0x0F) as usize) * 4;
+            (packet.get(header_len..).unwrap_or_default(), true)
+        }
+        _ => (packet, false),
+    };
+
+    icmp.first() == Some(&ECHO_REPLY)
+        && is_echo(icmp, sequence)
+        && (!check_identifier || icmp[4..6] == identifier.to_be_bytes())
+}
+
+// Sequence and payload match one of our requests
+fn is_echo(icmp: &[u8], sequence: u16) -> bool {
+    icmp.len() >= 8
+        && u16::from_be_bytes([icmp[6], icmp[7]]) == sequence
+        && icmp[8..].starts_with(PAYLOAD)
+}
+
+// RFC 1071 internet checksum
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in data.chunks(2) {
+        let word = match chunk {
+            [hi, lo] => u16::from_be_bytes([*hi, *lo]),
+            [hi] => u16::from_be_bytes([*hi, 0]),
+            _ => 0,
+        };
+        sum += word as u32;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The request as the device echoes it back
+    fn reply(identifier: u16, sequence: u16) -> Vec<u8> {
+        let mut packet = echo_request(identifier, sequence);
+        packet[0] = ECHO_REPLY;
+        packet[2..4].copy_from_slice(&[0, 0]);
+        let checksum = checksum(&packet);
+        packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+        packet
+    }
+
+    // A minimal 20-byte IPv4 header in front of `icmp`
+    fn with_ip_header(icmp: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 1, 0, 0, 192, 168, 1, 20, 192, 168, 1, 10];
+        packet.extend_from_slice(icmp);
+        packet
+    }
+
+    #[test]
+    fn rfc1071_checksum() {
+        // The example from RFC 1071 section 3: the sum is 0xddf2
+        assert_eq!(checksum(&[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]), !0xddf2);
+        // Odd lengths pad with a zero byte
+        assert_eq!(checksum(&[0x00, 0x01, 0xf2]), !0xf201);
+        // A packet with its checksum filled in sums to zero
+        assert_eq!(checksum(&echo_request(0x1234, 7)), 0);
+    }
+
+    #[test]
+    fn datagram_reply() {
+        // Linux rewrites the identifier, so any matches without a header
+        assert!(is_reply_to(&reply(0x1234, 7), 0x1234, 7));
+        assert!(is_reply_to(&reply(0x9999, 7), 0x1234, 7));
+        assert!(!is_reply_to(&reply(0x1234, 8), 0x1234, 7));
+        assert!(!is_reply_to(&echo_request(0x1234, 7), 0x1234, 7));
+    }
+
+    #[test]
+    fn raw_reply() {
+        assert!(is_reply_to(&with_ip_header(&reply(0x1234, 7)), 0x1234, 7));
+        assert!(!is_reply_to(&with_ip_header(&reply(0x1234, 8)), 0x1234, 7));
+        assert!(!is_reply_to(&with_ip_header(&reply(0x4321, 7)), 0x1234, 7));
+    }
+
+    #[test]
+    fn truncated_reply() {
+        let packet = with_ip_header(&reply(0x1234, 7));
+        assert!(!is_reply_to(&packet[..26], 0x1234, 7));
+        assert!(!is_reply_to(&[], 0x1234, 7));
+    }
+}
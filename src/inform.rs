@@ -0,0 +1,265 @@
+use crate::cancel::CancelToken;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+// Inform packets start with this, followed by the packet version
+const MAGIC: &[u8; 4] = b"TNBU";
+
+// Magic, packet version, MAC and flags
+const HEADER_LEN: usize = 16;
+
+// Requests larger than this are dropped; real informs are a few KB
+const MAX_REQUEST: usize = 64 * 1024;
+
+/// Header flag: the payload is encrypted.
+pub const FLAG_ENCRYPTED: u16 = 0x01;
+/// Header flag: the payload is zlib-compressed.
+pub const FLAG_ZLIB: u16 = 0x02;
+/// Header flag: the payload is snappy-compressed.
+pub const FLAG_SNAPPY: u16 = 0x04;
+/// Header flag: the payload is encrypted with AES-GCM.
+pub const FLAG_GCM: u16 = 0x08;
+
+/// The unencrypted header of an inform packet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InformHeader {
+    pub version: u32,
+    /// Uppercase `AA:BB:CC:DD:EE:FF`.
+    pub mac: String,
+    pub flags: u16,
+}
+
+/// An inform POST that reached [`serve`].
+#[derive(Debug, Clone)]
+pub struct InformReceived {
+    pub from: IpAddr,
+    pub header: InformHeader,
+}
+
+/// Decode the header of an inform packet body. The payload after it needs
+/// the device's auth key and isn't looked at.
+pub fn parse_header(body: &[u8]) -> Option<InformHeader> {
+    if body.len() < HEADER_LEN || &body[..4] != MAGIC {
+        return None;
+    }
+    let version = u32::from_be_bytes([body[4], body[5], body[6], body[7]]);
+    let mac = body[8..14]
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":");
+    let flags = u16::from_be_bytes([body[14], body[15]]);
+    Some(InformHeader { version, mac, flags })
+}
+
+/// The port devices will inform to for `controller_url`, e.g. 8081 for
+/// `http://192.168.1.1:8081`. Devices inform over plain HTTP on 8080 even
+/// when the controller URL is `https`, so that's the default when the URL
+/// doesn't name a port.
+pub fn port_from_url(controller_url: &str) -> u16 {
+    let rest = controller_url
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(controller_url);
+    let authority = rest.split('/').next().unwrap_or_default();
+    // An IPv6 literal has colons of its own, so only look past its bracket
+    let after_host = match authority.rfind(']') {
+        Some(end) => &authority[end + 1..],
+        None => authority,
+    };
+    after_host
+        .rsplit_once(':')
+        .and_then(|(_, port)| port.parse().ok())
+        .unwrap_or(8080)
+}
+
+/// Accept inform POSTs on `port`, over IPv4 and (where the host has it)
+/// IPv6, until `cancel` is cancelled, sending each one with a valid header
+/// to `events`.
+///
+/// The device gets a 404 back, since a real reply needs its auth key, and
+/// keeps informing as it would while the controller is down. Fails if the
+/// port can't be bound, e.g. because a controller is already running here.
+pub async fn serve(
+    port: u16,
+    cancel: &CancelToken,
+    events: tokio::sync::mpsc::UnboundedSender<InformReceived>,
+) -> Result<(), String> {
+    let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
+        .await
+        .map_err(|e| format!("Can't listen on port {}: {}", port, e))?;
+    // IPv6 devices inform here too; a host without IPv6 just skips it
+    let listener_v6 = bind_v6_only(port).ok();
+
+    let mut connections = tokio::task::JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = accept(&listener, listener_v6.as_ref()) => {
+                let Ok((stream, from)) = accepted else {
+                    continue;
+                };
+                let events = events.clone();
+                connections.spawn(async move {
+                    if let Some(header) = handle_connection(stream).await {
+                        events.send(InformReceived { from: from.ip(), header }).ok();
+                    }
+                });
+            }
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            _ = cancel.cancelled() => {
+                connections.abort_all();
+                return Ok(());
+            }
+        }
+    }
+}
+
+// A listener on `[::]` that leaves IPv4 to the 0.0.0.0 one
+fn bind_v6_only(port: u16) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(true)?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    socket.listen(128)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
+
+// The next connection on either listener
+async fn accept(v4: &TcpListener, v6: Option<&TcpListener>) -> std::io::Result<(TcpStream, SocketAddr)> {
+    match v6 {
+        Some(v6) => tokio::select! {
+            accepted = v4.accept() => accepted,
+            accepted = v6.accept() => accepted,
+        },
+        None => v4.accept().await,
+    }
+}
+
+// How long a client gets to send its whole request
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// Read one request and answer it. Returns the inform header if it was a
+// POST to /inform.
+async fn handle_connection(mut stream: TcpStream) -> Option<InformHeader> {
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream))
+        .await
+        .ok()
+        .flatten();
+
+    let header = request.and_then(|(method, path, body)| {
+        let path = path.split('?').next().unwrap_or_default();
+        if method == "POST" && path == "/inform" {
+            parse_header(&body)
+        } else {
+            None
+        }
+    });
+
+    let response = "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    stream.write_all(response.as_bytes()).await.ok();
+    stream.shutdown().await.ok();
+    header
+}
+
+// Method, path and body of an HTTP/1.x request with a Content-Length
+async fn read_request(stream: &mut TcpStream) -> Option<(String, String, Vec<u8>)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_REQUEST {
+            return None;
+        }
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let content_length = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > MAX_REQUEST {
+        return None;
+    }
+
+    let mut body = buf.split_off(head_end);
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Some((method, path, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(mac: [u8; 6], flags: u16) -> Vec<u8> {
+        let mut body = MAGIC.to_vec();
+        body.extend_from_slice(&0u32.to_be_bytes());
+        body.extend_from_slice(&mac);
+        body.extend_from_slice(&flags.to_be_bytes());
+        // Start of the encrypted payload
+        body.extend_from_slice(&[0x5a; 24]);
+        body
+    }
+
+    #[test]
+    fn valid_header() {
+        let body = header([0x24, 0x5a, 0x4c, 0x01, 0xab, 0xcd], FLAG_ENCRYPTED | FLAG_SNAPPY);
+        let parsed = parse_header(&body).unwrap();
+        assert_eq!(parsed.version, 0);
+        assert_eq!(parsed.mac, "24:5A:4C:01:AB:CD");
+        assert_eq!(parsed.flags & FLAG_ENCRYPTED, FLAG_ENCRYPTED);
+        assert_eq!(parsed.flags & FLAG_SNAPPY, FLAG_SNAPPY);
+        assert_eq!(parsed.flags & (FLAG_ZLIB | FLAG_GCM), 0);
+    }
+
+    #[test]
+    fn wrong_magic() {
+        let mut body = header([0; 6], 0);
+        body[..4].copy_from_slice(b"HTTP");
+        assert_eq!(parse_header(&body), None);
+    }
+
+    #[test]
+    fn short_body() {
+        let body = header([0xfc, 0xec, 0xda, 0, 0, 1], 0);
+        assert!(parse_header(&body[..HEADER_LEN]).is_some());
+        assert_eq!(parse_header(&body[..HEADER_LEN - 1]), None);
+        assert_eq!(parse_header(b""), None);
+    }
+
+    #[test]
+    fn ports_from_urls() {
+        assert_eq!(port_from_url("http://192.168.1.1:8080"), 8080);
+        assert_eq!(port_from_url("http://unifi.lan:8081/"), 8081);
+        assert_eq!(port_from_url("http://192.168.1.1"), 8080);
+        assert_eq!(port_from_url("192.168.1.1"), 8080);
+        assert_eq!(port_from_url("https://unifi.example.com"), 8080);
+        assert_eq!(port_from_url("https://unifi.example.com:8443/"), 8443);
+        assert_eq!(port_from_url("http://[fd00::1]:8080"), 8080);
+        assert_eq!(port_from_url("http://[fd00::1]"), 8080);
+        assert_eq!(port_from_url("https://[2001:db8::443]"), 8080);
+    }
+}
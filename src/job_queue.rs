@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+
+/// First-in, first-out queue of device jobs that runs at most `limit` at a
+/// time. Jobs can be pushed while others are running; the caller starts
+/// whatever [`JobQueue::start_ready`] hands back and reports each completion
+/// with [`JobQueue::finish`].
+#[derive(Debug)]
+pub struct JobQueue<J> {
+    waiting: VecDeque<J>,
+    running: usize,
+    limit: usize,
+}
+
+impl<J> JobQueue<J> {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            waiting: VecDeque::new(),
+            running: 0,
+            limit: limit.max(1),
+        }
+    }
+
+    /// Change the parallelism limit. Running jobs are not interrupted; a lower
+    /// limit takes effect as they finish.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit.max(1);
+    }
+
+    pub fn push(&mut self, job: J) {
+        self.waiting.push_back(job);
+    }
+
+    /// Drop waiting jobs for which `keep` returns false. Running jobs are
+    /// unaffected.
+    pub fn retain(&mut self, keep: impl FnMut(&J) -> bool) {
+        self.waiting.retain(keep);
+    }
+
+    /// Take as many waiting jobs as there are free slots and count them as
+    /// running.
+    pub fn start_ready(&mut self) -> Vec<J> {
+        let free = self.limit.saturating_sub(self.running);
+        let count = free.min(self.waiting.len());
+        self.running += count;
+        self.waiting.drain(..count).collect()
+    }
+
+    /// Record that a running job has completed, freeing its slot.
+    pub fn finish(&mut self) {
+        self.running = self.running.saturating_sub(1);
+    }
+
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    pub fn running(&self) -> usize {
+        self.running
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.running == 0 && self.waiting.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_of(limit: usize, jobs: usize) -> JobQueue<usize> {
+        let mut queue = JobQueue::new(limit);
+        for job in 0..jobs {
+            queue.push(job);
+        }
+        queue
+    }
+
+    #[test]
+    fn start_ready_respects_the_limit() {
+        let mut queue = queue_of(2, 5);
+        assert_eq!(queue.start_ready(), vec![0, 1]);
+        assert_eq!(queue.running(), 2);
+        assert_eq!(queue.waiting(), 3);
+        // No free slots until something finishes
+        assert!(queue.start_ready().is_empty());
+    }
+
+    #[test]
+    fn finish_starts_the_next_job() {
+        let mut queue = queue_of(2, 3);
+        queue.start_ready();
+        queue.finish();
+        assert_eq!(queue.running(), 1);
+        assert_eq!(queue.start_ready(), vec![2]);
+        assert_eq!(queue.running(), 2);
+        assert_eq!(queue.waiting(), 0);
+
+        queue.finish();
+        queue.finish();
+        assert!(queue.is_idle());
+    }
+
+    #[test]
+    fn lower_limit_waits_for_running_jobs() {
+        let mut queue = queue_of(4, 6);
+        assert_eq!(queue.start_ready().len(), 4);
+        queue.set_limit(2);
+        assert_eq!(queue.running(), 4);
+
+        // Two finish, but that only brings it down to the new limit
+        queue.finish();
+        queue.finish();
+        assert!(queue.start_ready().is_empty());
+
+        queue.finish();
+        assert_eq!(queue.start_ready(), vec![4]);
+        assert_eq!(queue.running(), 2);
+    }
+
+    #[test]
+    fn retain_only_drops_waiting_jobs() {
+        let mut queue = queue_of(2, 5);
+        assert_eq!(queue.start_ready(), vec![0, 1]);
+
+        // Cancel everything: only the queued jobs can be dropped
+        queue.retain(|_| false);
+        assert_eq!(queue.waiting(), 0);
+        assert_eq!(queue.running(), 2);
+        assert!(!queue.is_idle());
+
+        queue.push(5);
+        queue.push(6);
+        queue.retain(|&job| job != 5);
+        queue.finish();
+        assert_eq!(queue.start_ready(), vec![6]);
+    }
+
+    #[test]
+    fn zero_limit_still_runs_one() {
+        let mut queue = queue_of(0, 2);
+        assert_eq!(queue.start_ready(), vec![0]);
+        queue.set_limit(0);
+        queue.finish();
+        assert_eq!(queue.start_ready(), vec![1]);
+    }
+}
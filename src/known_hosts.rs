@@ -0,0 +1,248 @@
+use crate::config::HostKeyPolicy;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+/// An SSH host key as shown by `ssh-keygen -l`: type plus SHA256 fingerprint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostKey {
+    pub key_type: String,
+    pub fingerprint: String,
+}
+
+impl std::fmt::Display for HostKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.key_type, self.fingerprint)
+    }
+}
+
+/// Outcome of checking a presented key against the store.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// The key matches the stored one, or checking is off.
+    Trusted,
+    /// First connection under TOFU; the key has been saved.
+    Learned,
+    /// No stored key and the policy is strict.
+    Unknown,
+    /// A different key is stored for this device.
+    Mismatch { stored: HostKey },
+}
+
+// One entry per line: `<ip> <mac or -> <key type> <fingerprint>`
+struct Entry {
+    ip: String,
+    mac: String,
+    key: HostKey,
+}
+
+// Held across each load-modify-save, so parallel adoption jobs learning keys
+// at the same time don't overwrite each other's entries
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+fn lock_store() -> MutexGuard<'static, ()> {
+    STORE_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn known_hosts_path() -> PathBuf {
+    crate::config::app_config_dir().join("known_hosts.txt")
+}
+
+fn load() -> Vec<Entry> {
+    let contents = fs::read_to_string(known_hosts_path()).unwrap_or_default();
+    contents
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() != 4 || parts[0].starts_with('#') {
+                return None;
+            }
+            Some(Entry {
+                ip: parts[0].to_string(),
+                mac: parts[1].to_string(),
+                key: HostKey {
+                    key_type: parts[2].to_string(),
+                    fingerprint: parts[3].to_string(),
+                },
+            })
+        })
+        .collect()
+}
+
+fn save(entries: &[Entry]) {
+    let mut content = String::new();
+    for entry in entries {
+        content.push_str(&format!(
+            "{} {} {} {}\n",
+            entry.ip, entry.mac, entry.key.key_type, entry.key.fingerprint
+        ));
+    }
+    let _ = fs::write(known_hosts_path(), content);
+}
+
+fn normalize_mac(mac: Option<&str>) -> Option<String> {
+    mac.filter(|m| !m.is_empty() && *m != "Unknown")
+        .map(|m| m.to_uppercase())
+}
+
+// A MAC match wins over an IP match so DHCP reassignments don't look like a
+// changed key. An IP entry recorded for a different MAC still counts: an
+// ARP spoofer shows up with its own MAC, and mustn't be learned over the
+// real device's key without an explicit trust.
+fn find<'a>(entries: &'a [Entry], ip: &str, mac: Option<&str>) -> Option<&'a Entry> {
+    if let Some(mac) = mac {
+        if let Some(entry) = entries.iter().find(|e| e.mac == mac) {
+            return Some(entry);
+        }
+    }
+    entries.iter().find(|e| e.ip == ip)
+}
+
+/// Check `key` for the device at `ip` (and `mac`, when known) under `policy`.
+pub fn verify(ip: &str, mac: Option<&str>, key: &HostKey, policy: HostKeyPolicy) -> Verdict {
+    if policy == HostKeyPolicy::Off {
+        return Verdict::Trusted;
+    }
+
+    let mac = normalize_mac(mac);
+    let _guard = lock_store();
+    let mut entries = load();
+    let verdict = judge(&entries, ip, mac.as_deref(), key, policy);
+    if verdict == Verdict::Learned {
+        replace(&mut entries, ip, mac, key);
+        save(&entries);
+    }
+    verdict
+}
+
+// The verdict for `key` against `entries`. `Learned` means the caller
+// should store it.
+fn judge(entries: &[Entry], ip: &str, mac: Option<&str>, key: &HostKey, policy: HostKeyPolicy) -> Verdict {
+    match find(entries, ip, mac) {
+        Some(entry) if entry.key == *key => Verdict::Trusted,
+        Some(entry) => Verdict::Mismatch {
+            stored: entry.key.clone(),
+        },
+        None if policy == HostKeyPolicy::Strict => Verdict::Unknown,
+        None => Verdict::Learned,
+    }
+}
+
+/// Store `key` for the device, replacing any entry with the same IP or MAC.
+pub fn trust(ip: &str, mac: Option<&str>, key: &HostKey) {
+    let mac = normalize_mac(mac);
+    let _guard = lock_store();
+    let mut entries = load();
+    replace(&mut entries, ip, mac, key);
+    save(&entries);
+}
+
+fn replace(entries: &mut Vec<Entry>, ip: &str, mac: Option<String>, key: &HostKey) {
+    entries.retain(|e| e.ip != ip && mac.as_deref().is_none_or(|m| e.mac != m));
+    entries.push(Entry {
+        ip: ip.to_string(),
+        mac: mac.unwrap_or_else(|| String::from("-")),
+        key: key.clone(),
+    });
+}
+
+/// OpenSSH-style `SHA256:...` fingerprint (unpadded base64) of a key hash.
+pub fn format_fingerprint(sha256: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::from("SHA256:");
+    for chunk in sha256.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3F] as char);
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(fingerprint: &str) -> HostKey {
+        HostKey {
+            key_type: String::from("ssh-ed25519"),
+            fingerprint: fingerprint.to_string(),
+        }
+    }
+
+    fn entry(ip: &str, mac: &str, fingerprint: &str) -> Entry {
+        Entry {
+            ip: ip.to_string(),
+            mac: mac.to_string(),
+            key: key(fingerprint),
+        }
+    }
+
+    #[test]
+    fn mac_entry_wins_over_ip_entry() {
+        let entries = vec![
+            entry("192.168.1.20", "-", "SHA256:by-ip"),
+            entry("192.168.1.99", "24:5A:4C:01:AB:CD", "SHA256:by-mac"),
+        ];
+        let found = find(&entries, "192.168.1.20", Some("24:5A:4C:01:AB:CD")).unwrap();
+        assert_eq!(found.key.fingerprint, "SHA256:by-mac");
+
+        // Unknown MAC, so only the IP entry can match
+        let found = find(&entries, "192.168.1.20", None).unwrap();
+        assert_eq!(found.key.fingerprint, "SHA256:by-ip");
+    }
+
+    #[test]
+    fn ip_entry_for_another_mac() {
+        let entries = vec![entry("192.168.1.20", "24:5A:4C:01:AB:CD", "SHA256:old")];
+        assert!(find(&entries, "192.168.1.20", Some("FC:EC:DA:00:00:01")).is_some());
+        assert!(find(&entries, "192.168.1.20", None).is_some());
+    }
+
+    #[test]
+    fn spoofed_mac_is_not_learned() {
+        let entries = vec![entry("192.168.1.20", "24:5A:4C:01:AB:CD", "SHA256:real")];
+        // Someone else answering for the address, with their own MAC and key
+        let verdict = judge(&entries, "192.168.1.20", Some("FC:EC:DA:00:00:01"), &key("SHA256:spoof"), HostKeyPolicy::Tofu);
+        assert_eq!(verdict, Verdict::Mismatch { stored: key("SHA256:real") });
+
+        // A new device at a fresh address is still learned
+        let verdict = judge(&entries, "192.168.1.21", Some("FC:EC:DA:00:00:01"), &key("SHA256:new"), HostKeyPolicy::Tofu);
+        assert_eq!(verdict, Verdict::Learned);
+        let verdict = judge(&entries, "192.168.1.21", None, &key("SHA256:new"), HostKeyPolicy::Strict);
+        assert_eq!(verdict, Verdict::Unknown);
+    }
+
+    #[test]
+    fn trust_replaces_existing_entry() {
+        let mut entries = vec![
+            entry("192.168.1.20", "24:5A:4C:01:AB:CD", "SHA256:old"),
+            entry("192.168.1.21", "-", "SHA256:other"),
+        ];
+        // Same device, new address after a DHCP change
+        replace(&mut entries, "192.168.1.30", Some(String::from("24:5A:4C:01:AB:CD")), &key("SHA256:new"));
+        assert_eq!(entries.len(), 2);
+        let found = find(&entries, "192.168.1.30", Some("24:5A:4C:01:AB:CD")).unwrap();
+        assert_eq!(found.key.fingerprint, "SHA256:new");
+        assert!(find(&entries, "192.168.1.20", None).is_none());
+
+        // Same address, MAC still unknown
+        replace(&mut entries, "192.168.1.21", None, &key("SHA256:rekeyed"));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(find(&entries, "192.168.1.21", None).unwrap().key.fingerprint, "SHA256:rekeyed");
+    }
+
+    #[test]
+    fn fingerprint_matches_openssh() {
+        // SHA-256 of the empty string
+        let hash = [
+            0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f, 0xb9, 0x24,
+            0x27, 0xae, 0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b, 0x78, 0x52, 0xb8, 0x55,
+        ];
+        assert_eq!(format_fingerprint(&hash), "SHA256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU");
+        assert_eq!(format_fingerprint(b"ab"), "SHA256:YWI");
+        assert_eq!(format_fingerprint(b"abc"), "SHA256:YWJj");
+    }
+}
@@ -0,0 +1,54 @@
+//! UniFi device discovery and adoption engine.
+//!
+//! This crate holds everything that does not depend on the GUI so other
+//! programs (the `unifi-adopt-cli` binary, internal tooling, tests) can link
+//! against it directly:
+//!
+//! - [`network_scanner`] - ping/SSH sweep of an IP range
+//! - [`arp`] - ARP sweep of the local subnet (Linux)
+//! - [`discovery`] - UniFi discovery protocol (UDP 10001)
+//! - [`fingerprint`] - TCP port probes and SSH/HTTP banner grabbing
+//! - [`hostname`] - reverse DNS and mDNS name lookup
+//! - [`dhcp_leases`] - dnsmasq/ISC dhcpd lease file reader
+//! - [`neighbor_table`] - reader for the OS ARP/neighbor table
+//! - [`icmp`] - in-process ICMP echo used by the scanner
+//! - [`inform`] - minimal inform endpoint that notices devices calling home
+//! - [`ssh_handler`] - SSH into a device and run `set-inform`
+//! - [`cancel`] - cancellation token for scans and adoption sessions
+//! - [`rate_limit`] - shared packet rate limit for scans
+//! - [`job_queue`] - bounded-concurrency queue for per-device jobs
+//! - [`device_info`] - parser for the device's own `info` report
+//! - [`known_hosts`] - SSH host key store for the adoption engine
+//! - [`oui_database`] - MAC address vendor lookup
+//! - [`config`] - persistent settings shared with the GUI
+//! - [`network_interface`] - local interface and IP range detection
+//! - [`models`] - device records produced by the scanner
+
+pub mod arp;
+pub mod cancel;
+pub mod config;
+pub mod device_info;
+pub mod dhcp_leases;
+pub mod discovery;
+pub mod fingerprint;
+pub mod hostname;
+pub mod icmp;
+pub mod inform;
+pub mod job_queue;
+pub mod known_hosts;
+pub mod models;
+pub mod neighbor_table;
+pub mod network_interface;
+pub mod network_scanner;
+pub mod oui_database;
+pub mod rate_limit;
+pub mod ssh_handler;
+
+pub use cancel::CancelToken;
+pub use config::{load_config, save_config, AppConfig};
+pub use models::{Device, DeviceKind, DeviceStatus};
+pub use network_scanner::{listen_for_announcements, scan_network, ScanEvent, ScanOptions, TargetSet};
+pub use oui_database::get_manufacturer;
+pub use device_info::DeviceInfo;
+pub use job_queue::JobQueue;
+pub use ssh_handler::{execute_adoption, identify_device, AdoptionError, AdoptionOptions, AdoptionReport};
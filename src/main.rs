@@ -1,21 +1,18 @@
 // Hide console window on Windows
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod config;
 mod messages;
-mod models;
-mod network_interface;
-mod network_scanner;
-mod oui_database;
-mod ssh_handler;
 mod styles;
 mod views;
 
 use iced::{executor, Application, Command, Element, Settings, Subscription, Theme};
 use iced::widget::text_editor;
-use messages::Message;
-use models::{Device, DeviceStatus, SettingsTab};
+use messages::{InterfaceChoice, Message, SettingsTab};
 use std::sync::Arc;
+use unifi_adopt::inform::{self, InformReceived};
+use unifi_adopt::known_hosts::{self, HostKey};
+use unifi_adopt::ssh_handler::{AdoptionOptions, AdoptionReport};
+use unifi_adopt::{config, fingerprint, listen_for_announcements, network_interface, network_scanner, ssh_handler, AdoptionError, Device, DeviceStatus, JobQueue, CancelToken, ScanEvent, ScanOptions, TargetSet};
 
 fn main() -> iced::Result {
     UnifiAdoption::run(Settings {
@@ -33,22 +30,252 @@ struct UnifiAdoption {
     log_output: String,
     log_editor_content: text_editor::Content,
     is_running: bool,
+    adoption_cancel: Option<CancelToken>,
     show_settings: bool,
     show_scan: bool,
     active_tab: SettingsTab,
     config: config::AppConfig,
     controller_url_input: String,
-    username_input: String,
-    password_input: String,
-    alt_username_input: String,
-    alt_password_input: String,
-    ip_range_start: String,
-    ip_range_end: String,
+    ssh_port_input: String,
+    credentials_input: Vec<config::Credentials>,
+    host_key_policy_input: config::HostKeyPolicy,
+    verify_adoption_input: bool,
+    verify_timeout_input: String,
+    reinform_input: bool,
+    reinform_interval_input: String,
+    max_parallel_jobs_input: String,
+    scan_max_in_flight_input: String,
+    scan_rate_input: String,
+    scan_ipv6_input: bool,
+    dhcp_lease_file_input: String,
+    scan_ports_input: String,
+    manual_changed_host_key: Option<(String, HostKey)>,
+    // Hosts, ranges and CIDR blocks to scan, as typed
+    scan_targets: String,
+    // Why the targets couldn't be scanned, shown under the field
+    scan_error: Option<String>,
+    // Interfaces offered in the picker, and the one probes are sent from
+    interfaces: Vec<network_interface::NetworkInterface>,
+    scan_interface: InterfaceChoice,
     devices: Vec<Device>,
     expanded_device_index: Option<usize>,
     is_scanning: bool,
+    scan_cancel: Option<CancelToken>,
+    // Addresses probed and total for the running scan
+    scan_progress: (usize, usize),
+    scan_event_receiver: Option<Arc<tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<ScanEvent>>>>,
+    // Passive discovery listener, while it's switched on
+    listen_cancel: Option<CancelToken>,
+    listen_event_receiver: Option<Arc<tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<ScanEvent>>>>,
+    listen_error: Option<String>,
+    // Bumped on every start so the subscription picks up the new receiver
+    listen_generation: u64,
+    // Built-in inform endpoint, while it's switched on
+    inform_cancel: Option<CancelToken>,
+    inform_event_receiver: Option<Arc<tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<InformReceived>>>>,
+    inform_error: Option<String>,
+    // Bumped on every start so the subscription picks up the new receiver
+    inform_generation: u64,
     progress_receiver: Option<Arc<tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<String>>>>,
     device_progress_receivers: std::collections::HashMap<usize, Arc<tokio::sync::Mutex<tokio::sync::mpsc::UnboundedReceiver<String>>>>,
+    jobs: JobQueue<QueuedJob>,
+    // Tokens for the jobs currently running, by device index
+    job_cancels: std::collections::HashMap<usize, CancelToken>,
+}
+
+type DeviceJob = fn(
+    &str,
+    Option<&str>,
+    &AdoptionOptions,
+    Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    &CancelToken,
+) -> Result<AdoptionReport, AdoptionError>;
+
+type JobComplete = fn(usize, Result<AdoptionReport, AdoptionError>) -> Message;
+
+// A device waiting in (or running from) the job queue
+#[derive(Clone, Copy)]
+struct QueuedJob {
+    index: usize,
+    job: DeviceJob,
+    on_complete: JobComplete,
+}
+
+impl UnifiAdoption {
+    // Add a host from a scan or an announcement. One that's already listed
+    // is updated in place; new ones go in address order, or at the end while
+    // jobs are running so their indices stay valid.
+    fn add_device(&mut self, device: Device) {
+        if let Some(existing) = self.devices.iter_mut().find(|d| d.ip == device.ip) {
+            if existing.mac == "Unknown" {
+                existing.mac = device.mac;
+                existing.company = device.company;
+            }
+            existing.has_ssh |= device.has_ssh;
+            existing.rtt = device.rtt.or(existing.rtt);
+            existing.discovery = device.discovery.or(existing.discovery.take());
+            existing.hostname = device.hostname.or(existing.hostname.take());
+            if !device.ports.is_empty() {
+                existing.ports = device.ports;
+            }
+            return;
+        }
+
+        let position = if self.jobs.is_idle() {
+            self.devices
+                .iter()
+                .position(|d| d.sort_key() > device.sort_key())
+                .unwrap_or(self.devices.len())
+        } else {
+            self.devices.len()
+        };
+        if let Some(expanded) = self.expanded_device_index.as_mut() {
+            if *expanded >= position {
+                *expanded += 1;
+            }
+        }
+        self.devices.insert(position, device);
+    }
+
+    // Start (or restart) the inform endpoint. Devices inform to the
+    // controller URL, so it listens on that URL's port.
+    fn start_inform_endpoint(&mut self) -> Command<Message> {
+        if let Some(cancel) = self.inform_cancel.take() {
+            cancel.cancel();
+        }
+        let port = inform::port_from_url(&self.config.controller_url);
+        let cancel = CancelToken::new();
+        self.inform_cancel = Some(cancel.clone());
+        self.inform_error = None;
+        self.inform_generation += 1;
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.inform_event_receiver = Some(Arc::new(tokio::sync::Mutex::new(rx)));
+
+        Command::perform(
+            async move { inform::serve(port, &cancel, tx).await },
+            Message::InformStopped,
+        )
+    }
+
+    // Start (or restart) the passive discovery listener
+    fn start_listening(&mut self) -> Command<Message> {
+        if let Some(cancel) = self.listen_cancel.take() {
+            cancel.cancel();
+        }
+        let cancel = CancelToken::new();
+        self.listen_cancel = Some(cancel.clone());
+        self.listen_error = None;
+        self.listen_generation += 1;
+
+        // Devices are heard on every interface, so probes aren't pinned to one
+        let options = ScanOptions::from_config(&self.config);
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        self.listen_event_receiver = Some(Arc::new(tokio::sync::Mutex::new(rx)));
+
+        Command::perform(
+            async move { listen_for_announcements(&options, &cancel, tx).await },
+            Message::ListenStopped,
+        )
+    }
+
+    // Queue `job` for every selected device that isn't already queued or
+    // running, then start as many as the parallelism limit allows
+    fn enqueue_selected(&mut self, job: DeviceJob, on_complete: JobComplete) -> Command<Message> {
+        // Rows are still being inserted, which would shift job indices
+        if self.is_scanning {
+            return Command::none();
+        }
+
+        for (index, device) in self.devices.iter_mut().enumerate() {
+            let busy = matches!(device.status, DeviceStatus::Queued | DeviceStatus::InProgress);
+            if device.selected && !busy {
+                device.status = DeviceStatus::Queued;
+                device.logs = String::new();
+                device.changed_host_key = None;
+                self.jobs.push(QueuedJob { index, job, on_complete });
+            }
+        }
+
+        self.start_queued_jobs()
+    }
+
+    // Launch queued jobs into free slots, streaming each device's session
+    // into its log
+    fn start_queued_jobs(&mut self) -> Command<Message> {
+        let options = AdoptionOptions::from_config(&self.config);
+
+        let commands: Vec<Command<Message>> = self.jobs
+            .start_ready()
+            .into_iter()
+            .filter_map(|QueuedJob { index, job, on_complete }| {
+                let device = self.devices.get_mut(index)?;
+                device.status = DeviceStatus::InProgress;
+
+                let ip = device.ssh_target();
+                let mac = device.mac.clone();
+                let options = options.clone();
+
+                // Create channel for this device
+                let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+                self.device_progress_receivers.insert(index, Arc::new(tokio::sync::Mutex::new(rx)));
+
+                let cancel = CancelToken::new();
+                self.job_cancels.insert(index, cancel.clone());
+
+                Some(Command::perform(
+                    async move {
+                        tokio::task::spawn_blocking(move || job(&ip, Some(&mac), &options, Some(tx), &cancel))
+                            .await
+                            .unwrap()
+                    },
+                    move |result| on_complete(index, result),
+                ))
+            })
+            .collect();
+
+        Command::batch(commands)
+    }
+
+    // Stop a device's job: drop it from the queue if it hasn't started,
+    // otherwise signal the running session to close
+    fn cancel_job(&mut self, index: usize) {
+        let Some(device) = self.devices.get_mut(index) else {
+            return;
+        };
+        match device.status {
+            DeviceStatus::Queued => {
+                self.jobs.retain(|job| job.index != index);
+                device.status = DeviceStatus::Cancelled;
+                device.logs = String::from("Cancelled before it started\n");
+            }
+            DeviceStatus::InProgress => {
+                if let Some(cancel) = self.job_cancels.get(&index) {
+                    cancel.cancel();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Free the finished job's slot and start the next one
+    fn finish_job(&mut self, index: usize) -> Command<Message> {
+        self.device_progress_receivers.remove(&index);
+        self.job_cancels.remove(&index);
+        self.jobs.finish();
+        self.start_queued_jobs()
+    }
+}
+
+fn record_failure(device: &mut Device, err: AdoptionError) {
+    device.status = match err {
+        AdoptionError::Cancelled(_) => DeviceStatus::Cancelled,
+        _ => DeviceStatus::Error,
+    };
+    device.logs = err.log().to_string();
+    if let AdoptionError::HostKeyMismatch { address, presented, .. } = err {
+        device.changed_host_key = Some((address, presented));
+    }
 }
 
 impl Application for UnifiAdoption {
@@ -61,10 +288,10 @@ impl Application for UnifiAdoption {
         let config = config::load_config();
 
         // Auto-detect network and set IP range (like Angry IP Scanner)
-        let (ip_range_start, ip_range_end) = if let Some(network) = network_interface::get_default_network() {
-            (network.start_ip, network.end_ip)
+        let scan_targets = if let Some(network) = network_interface::get_default_network() {
+            network.cidr
         } else {
-            (String::from("192.168.1.1"), String::from("192.168.1.254"))
+            String::from("192.168.1.0/24")
         };
 
         let app = UnifiAdoption {
@@ -72,21 +299,47 @@ impl Application for UnifiAdoption {
             log_output: String::new(),
             log_editor_content: text_editor::Content::new(),
             is_running: false,
+            adoption_cancel: None,
             show_settings: false,
             show_scan: true,
             active_tab: SettingsTab::General,
             controller_url_input: config.controller_url.clone(),
-            username_input: config.ssh_username.clone(),
-            password_input: config.ssh_password.clone(),
-            alt_username_input: config.alt_ssh_username.clone(),
-            alt_password_input: config.alt_ssh_password.clone(),
-            ip_range_start,
-            ip_range_end,
+            ssh_port_input: config.ssh_port.to_string(),
+            credentials_input: config.credentials.clone(),
+            host_key_policy_input: config.host_key_policy,
+            verify_adoption_input: config.verify_adoption,
+            verify_timeout_input: config.verify_timeout_secs.to_string(),
+            reinform_input: config.reinform_until_connected,
+            reinform_interval_input: config.reinform_interval_secs.to_string(),
+            max_parallel_jobs_input: config.max_parallel_jobs.to_string(),
+            scan_max_in_flight_input: config.scan_max_in_flight.to_string(),
+            scan_rate_input: config.scan_probes_per_second.to_string(),
+            scan_ipv6_input: config.scan_ipv6,
+            dhcp_lease_file_input: config.dhcp_lease_file.clone(),
+            scan_ports_input: fingerprint::format_ports(&config.scan_ports),
+            manual_changed_host_key: None,
+            scan_targets,
+            scan_error: None,
+            interfaces: network_interface::get_local_networks(),
+            scan_interface: InterfaceChoice::Automatic,
             devices: Vec::new(),
             expanded_device_index: None,
             is_scanning: false,
+            scan_cancel: None,
+            scan_progress: (0, 0),
+            scan_event_receiver: None,
+            listen_cancel: None,
+            listen_event_receiver: None,
+            listen_error: None,
+            listen_generation: 0,
+            inform_cancel: None,
+            inform_event_receiver: None,
+            inform_error: None,
+            inform_generation: 0,
             progress_receiver: None,
             device_progress_receivers: std::collections::HashMap::new(),
+            jobs: JobQueue::new(config.max_parallel_jobs),
+            job_cancels: std::collections::HashMap::new(),
             config,
         };
         (app, Command::none())
@@ -111,10 +364,12 @@ impl Application for UnifiAdoption {
                 self.log_output = String::new();
                 self.log_editor_content = text_editor::Content::new();
 
+                self.manual_changed_host_key = None;
+
                 let ip = self.ip_address.clone();
-                let username = self.config.ssh_username.clone();
-                let password = self.config.ssh_password.clone();
-                let controller_url = self.config.controller_url.clone();
+                let options = AdoptionOptions::from_config(&self.config);
+                let cancel = CancelToken::new();
+                self.adoption_cancel = Some(cancel.clone());
 
                 // Create channel for progress updates
                 let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
@@ -126,73 +381,51 @@ impl Application for UnifiAdoption {
                         tokio::task::spawn_blocking(move || {
                             ssh_handler::execute_adoption(
                                 &ip,
-                                &username,
-                                &password,
-                                &controller_url,
+                                None,
+                                &options,
                                 Some(tx),
+                                &cancel,
                             )
                         })
                         .await
                         .unwrap()
-                        .map(|output| output)
-                        .map_err(|e| format!("{}", e))
                     },
                     Message::AdoptionComplete,
                 )
             }
-            Message::AdoptAgainClicked => {
-                if self.is_running || self.ip_address.is_empty() {
-                    return Command::none();
+            Message::CancelAdoption => {
+                if let Some(cancel) = &self.adoption_cancel {
+                    cancel.cancel();
                 }
-
-                self.is_running = true;
-                self.log_output = String::new();
-                self.log_editor_content = text_editor::Content::new();
-
-                let ip = self.ip_address.clone();
-                let username = self.config.alt_ssh_username.clone();
-                let password = self.config.alt_ssh_password.clone();
-                let controller_url = self.config.controller_url.clone();
-
-                // Create channel for progress updates
-                let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-                self.progress_receiver = Some(Arc::new(tokio::sync::Mutex::new(rx)));
-
-                Command::perform(
-                    async move {
-                        // Run SSH in a blocking task so it doesn't block the async runtime
-                        tokio::task::spawn_blocking(move || {
-                            ssh_handler::execute_adoption(
-                                &ip,
-                                &username,
-                                &password,
-                                &controller_url,
-                                Some(tx),
-                            )
-                        })
-                        .await
-                        .unwrap()
-                        .map(|output| output)
-                        .map_err(|e| format!("{}", e))
-                    },
-                    Message::AdoptionComplete,
-                )
+                Command::none()
             }
             Message::AdoptionComplete(result) => {
                 self.is_running = false;
+                self.adoption_cancel = None;
                 self.progress_receiver = None; // Stop subscription
                 match result {
-                    Ok(log) => {
-                        self.log_output = log;
+                    Ok(report) => {
+                        self.log_output = report.log;
                         self.log_editor_content = text_editor::Content::with_text(&self.log_output);
                     }
-                    Err(log) => {
-                        self.log_output = log;
+                    Err(err) => {
+                        self.log_output = err.log().to_string();
                         self.log_editor_content = text_editor::Content::with_text(&self.log_output);
+                        if let AdoptionError::HostKeyMismatch { address, presented, .. } = err {
+                            self.manual_changed_host_key = Some((address, presented));
+                        }
                     }
                 }
                 Command::none()
             }
+            Message::TrustManualHostKey => {
+                if let Some((address, key)) = self.manual_changed_host_key.take() {
+                    known_hosts::trust(&address, None, &key);
+                    self.log_output.push_str(&format!("\nTrusted new host key {} for {}\n", key, address));
+                    self.log_editor_content = text_editor::Content::with_text(&self.log_output);
+                }
+                Command::none()
+            }
             Message::LogUpdate(line) => {
                 self.log_output.push_str(&line);
                 // Also update the editor content for display
@@ -223,45 +456,177 @@ impl Application for UnifiAdoption {
                 Command::none()
             }
             Message::SaveSettings => {
+                let inform_port = inform::port_from_url(&self.config.controller_url);
                 self.config.controller_url = self.controller_url_input.clone();
-                self.config.ssh_username = self.username_input.clone();
-                self.config.ssh_password = self.password_input.clone();
-                self.config.alt_ssh_username = self.alt_username_input.clone();
-                self.config.alt_ssh_password = self.alt_password_input.clone();
+                self.config.credentials = self.credentials_input.clone();
+                if let Ok(port) = self.ssh_port_input.trim().parse() {
+                    if port > 0 {
+                        self.config.ssh_port = port;
+                    }
+                }
+                self.config.host_key_policy = self.host_key_policy_input;
+                self.config.verify_adoption = self.verify_adoption_input;
+                if let Ok(secs) = self.verify_timeout_input.trim().parse() {
+                    self.config.verify_timeout_secs = secs;
+                }
+                self.config.reinform_until_connected = self.reinform_input;
+                if let Ok(secs) = self.reinform_interval_input.trim().parse() {
+                    self.config.reinform_interval_secs = secs;
+                }
+                if let Ok(count) = self.max_parallel_jobs_input.trim().parse() {
+                    self.config.max_parallel_jobs = count;
+                }
+                if let Ok(count) = self.scan_max_in_flight_input.trim().parse() {
+                    self.config.scan_max_in_flight = count;
+                }
+                if let Ok(rate) = self.scan_rate_input.trim().parse() {
+                    self.config.scan_probes_per_second = rate;
+                }
+                self.config.scan_ipv6 = self.scan_ipv6_input;
+                self.config.dhcp_lease_file = self.dhcp_lease_file_input.trim().to_string();
+                if let Ok(ports) = fingerprint::parse_ports(&self.scan_ports_input) {
+                    self.config.scan_ports = ports;
+                }
+                self.jobs.set_limit(self.config.max_parallel_jobs);
                 config::save_config(&self.config);
                 self.show_settings = false;
-                Command::none()
+
+                // Move a running inform endpoint to the new controller port
+                let restart_inform = if self.inform_cancel.is_some()
+                    && inform::port_from_url(&self.config.controller_url) != inform_port
+                {
+                    self.start_inform_endpoint()
+                } else {
+                    Command::none()
+                };
+
+                // A higher limit frees slots for queued devices right away
+                Command::batch([restart_inform, self.start_queued_jobs()])
             }
             Message::ControllerUrlChanged(value) => {
                 self.controller_url_input = value;
                 Command::none()
             }
-            Message::UsernameChanged(value) => {
-                self.username_input = value;
+            Message::SshPortChanged(value) => {
+                self.ssh_port_input = value;
                 Command::none()
             }
-            Message::PasswordChanged(value) => {
-                self.password_input = value;
+            Message::HostKeyPolicySelected(policy) => {
+                self.host_key_policy_input = policy;
                 Command::none()
             }
-            Message::AltUsernameChanged(value) => {
-                self.alt_username_input = value;
+            Message::VerifyAdoptionToggled(value) => {
+                self.verify_adoption_input = value;
                 Command::none()
             }
-            Message::AltPasswordChanged(value) => {
-                self.alt_password_input = value;
+            Message::VerifyTimeoutChanged(value) => {
+                self.verify_timeout_input = value;
                 Command::none()
             }
-            Message::TabSelected(tab) => {
-                self.active_tab = tab;
+            Message::ReinformToggled(value) => {
+                self.reinform_input = value;
                 Command::none()
             }
-            Message::ScanClicked => {
-                self.show_scan = true;
+            Message::ReinformIntervalChanged(value) => {
+                self.reinform_interval_input = value;
                 Command::none()
             }
-            Message::CloseScan => {
-                self.show_scan = false;
+            Message::MaxParallelJobsChanged(value) => {
+                self.max_parallel_jobs_input = value;
+                Command::none()
+            }
+            Message::ScanMaxInFlightChanged(value) => {
+                self.scan_max_in_flight_input = value;
+                Command::none()
+            }
+            Message::ScanRateChanged(value) => {
+                self.scan_rate_input = value;
+                Command::none()
+            }
+            Message::ScanIpv6Toggled(value) => {
+                self.scan_ipv6_input = value;
+                Command::none()
+            }
+            Message::DhcpLeaseFileChanged(value) => {
+                self.dhcp_lease_file_input = value;
+                Command::none()
+            }
+            Message::ScanPortsChanged(value) => {
+                self.scan_ports_input = value;
+                Command::none()
+            }
+            Message::CredentialNameChanged(index, value) => {
+                if let Some(creds) = self.credentials_input.get_mut(index) {
+                    creds.name = value;
+                }
+                Command::none()
+            }
+            Message::CredentialUsernameChanged(index, value) => {
+                if let Some(creds) = self.credentials_input.get_mut(index) {
+                    creds.username = value;
+                }
+                Command::none()
+            }
+            Message::CredentialPasswordChanged(index, value) => {
+                if let Some(creds) = self.credentials_input.get_mut(index) {
+                    creds.password = value;
+                }
+                Command::none()
+            }
+            Message::CredentialKeyPathChanged(index, value) => {
+                if let Some(creds) = self.credentials_input.get_mut(index) {
+                    creds.key_path = value;
+                }
+                Command::none()
+            }
+            Message::CredentialKeyPassphraseChanged(index, value) => {
+                if let Some(creds) = self.credentials_input.get_mut(index) {
+                    creds.key_passphrase = value;
+                }
+                Command::none()
+            }
+            Message::CredentialUseAgentToggled(index, value) => {
+                if let Some(creds) = self.credentials_input.get_mut(index) {
+                    creds.use_agent = value;
+                }
+                Command::none()
+            }
+            Message::CredentialPortChanged(index, value) => {
+                if let Some(creds) = self.credentials_input.get_mut(index) {
+                    // Empty means the global port; anything else must be a port
+                    if value.trim().is_empty() {
+                        creds.port = None;
+                    } else if let Ok(port @ 1..) = value.trim().parse::<u16>() {
+                        creds.port = Some(port);
+                    }
+                }
+                Command::none()
+            }
+            Message::AddCredential => {
+                let name = format!("Credential set {}", self.credentials_input.len() + 1);
+                self.credentials_input.push(config::Credentials::with_password(&name, "", ""));
+                Command::none()
+            }
+            Message::RemoveCredential(index) => {
+                if index < self.credentials_input.len() {
+                    self.credentials_input.remove(index);
+                }
+                Command::none()
+            }
+            Message::MoveCredentialUp(index) => {
+                if index > 0 && index < self.credentials_input.len() {
+                    self.credentials_input.swap(index - 1, index);
+                }
+                Command::none()
+            }
+            Message::MoveCredentialDown(index) => {
+                if index + 1 < self.credentials_input.len() {
+                    self.credentials_input.swap(index, index + 1);
+                }
+                Command::none()
+            }
+            Message::TabSelected(tab) => {
+                self.active_tab = tab;
                 Command::none()
             }
             Message::ManualEntryClicked => {
@@ -272,38 +637,152 @@ impl Application for UnifiAdoption {
                 self.show_scan = true;
                 Command::none()
             }
-            Message::IpRangeStartChanged(value) => {
-                self.ip_range_start = value;
+            Message::ScanTargetsChanged(value) => {
+                self.scan_targets = value;
+                self.scan_error = None;
                 Command::none()
             }
-            Message::IpRangeEndChanged(value) => {
-                self.ip_range_end = value;
+            Message::InterfaceSelected(choice) => {
+                if let InterfaceChoice::Interface(net) = &choice {
+                    self.scan_targets = net.cidr.clone();
+                    self.scan_error = None;
+                }
+                self.scan_interface = choice;
                 Command::none()
             }
             Message::ScanDevices => {
+                // Jobs refer to devices by index, so keep the list until they finish
+                if self.is_scanning || !self.jobs.is_idle() {
+                    return Command::none();
+                }
+
+                // IPv6 scans can run on the attached links alone
+                let targets = match TargetSet::parse(&self.scan_targets) {
+                    Ok(targets) => targets,
+                    Err(_) if self.config.scan_ipv6 && self.scan_targets.trim().is_empty() => TargetSet::default(),
+                    Err(err) => {
+                        self.scan_error = Some(err);
+                        return Command::none();
+                    }
+                };
+                self.scan_error = None;
                 self.is_scanning = true;
                 self.devices.clear();
                 self.expanded_device_index = None;
 
-                let start = self.ip_range_start.clone();
-                let end = self.ip_range_end.clone();
+                // The listener only reports changes, so start it over to
+                // refill the cleared table
+                let listen = if self.listen_cancel.is_some() {
+                    self.start_listening()
+                } else {
+                    Command::none()
+                };
 
-                Command::perform(
-                    async move {
-                        network_scanner::scan_network(&start, &end).await
-                    },
-                    Message::ScanComplete,
-                )
+                let mut options = ScanOptions::from_config(&self.config);
+                if let InterfaceChoice::Interface(net) = &self.scan_interface {
+                    options.source_ip = net.ip.parse().ok();
+                }
+                let cancel = CancelToken::new();
+                self.scan_cancel = Some(cancel.clone());
+                self.scan_progress = (0, 0);
+
+                // Hosts are streamed into the table as they answer
+                let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+                self.scan_event_receiver = Some(Arc::new(tokio::sync::Mutex::new(rx)));
+
+                Command::batch([
+                    listen,
+                    Command::perform(
+                        async move {
+                            network_scanner::scan_network(&targets, &options, &cancel, Some(tx)).await
+                        },
+                        Message::ScanComplete,
+                    ),
+                ])
+            }
+            Message::CancelScan => {
+                if let Some(cancel) = &self.scan_cancel {
+                    cancel.cancel();
+                }
+                Command::none()
+            }
+            Message::ScanUpdate(events) => {
+                for event in events {
+                    match event {
+                        ScanEvent::Found(device) => self.add_device(*device),
+                        ScanEvent::Progress { probed, total } => {
+                            self.scan_progress = (probed, total);
+                        }
+                    }
+                }
+                Command::none()
             }
             Message::ScanComplete(result) => {
                 self.is_scanning = false;
+                self.scan_cancel = None;
+                self.scan_event_receiver = None;
+                // Most devices already came in as scan events, but the last
+                // ones may not have been read before the receiver was
+                // dropped; add_device skips the ones that are listed
                 match result {
                     Ok(devices) => {
-                        self.devices = devices;
+                        for device in devices {
+                            self.add_device(device);
+                        }
                     }
-                    Err(err) => {
-                        // Could add error handling here
-                        eprintln!("Scan error: {}", err);
+                    Err(err) => self.scan_error = Some(err),
+                }
+                Command::none()
+            }
+            Message::ListenToggled(enabled) => {
+                if enabled {
+                    return self.start_listening();
+                }
+                if let Some(cancel) = self.listen_cancel.take() {
+                    cancel.cancel();
+                }
+                self.listen_event_receiver = None;
+                Command::none()
+            }
+            Message::ListenStopped(result) => {
+                // A listener stopped on purpose has already been cleared
+                if let Err(err) = result {
+                    self.listen_cancel = None;
+                    self.listen_event_receiver = None;
+                    self.listen_error = Some(err);
+                }
+                Command::none()
+            }
+            Message::InformToggled(enabled) => {
+                if enabled {
+                    return self.start_inform_endpoint();
+                }
+                if let Some(cancel) = self.inform_cancel.take() {
+                    cancel.cancel();
+                }
+                self.inform_event_receiver = None;
+                Command::none()
+            }
+            Message::InformStopped(result) => {
+                // An endpoint stopped on purpose has already been cleared
+                if let Err(err) = result {
+                    self.inform_cancel = None;
+                    self.inform_event_receiver = None;
+                    self.inform_error = Some(err);
+                }
+                Command::none()
+            }
+            Message::InformUpdate(informs) => {
+                for received in informs {
+                    let from = received.from.to_string();
+                    // Link-local devices are listed with their scope, which
+                    // the peer address doesn't carry
+                    let device = self.devices.iter_mut().find(|d| {
+                        d.mac.eq_ignore_ascii_case(&received.header.mac)
+                            || d.ip.split('%').next() == Some(from.as_str())
+                    });
+                    if let Some(device) = device {
+                        device.inform_received = true;
                     }
                 }
                 Command::none()
@@ -332,139 +811,64 @@ impl Application for UnifiAdoption {
                 }
                 Command::none()
             }
-            Message::DeviceStatusIconClicked(_index) => {
-                // No longer used - row click handles expansion
+            Message::AdoptSelected => {
+                self.enqueue_selected(ssh_handler::execute_adoption, Message::DeviceAdoptionComplete)
+            }
+            Message::CancelDevice(index) => {
+                self.cancel_job(index);
                 Command::none()
             }
-            Message::AdoptSelectedDefault => {
-                // Mark all selected devices as in progress
-                for device in &mut self.devices {
-                    if device.selected {
-                        device.status = DeviceStatus::InProgress;
-                        device.logs = String::new();
-                    }
-                }
-
-                // Collect selected devices for parallel adoption
-                let selected_devices: Vec<(usize, String)> = self.devices
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, d)| d.selected)
-                    .map(|(i, d)| (i, d.ip.clone()))
-                    .collect();
-
-                let username = self.config.ssh_username.clone();
-                let password = self.config.ssh_password.clone();
-                let controller_url = self.config.controller_url.clone();
-
-                // Clear old receivers
-                self.device_progress_receivers.clear();
-
-                // Launch parallel adoption tasks with progress channels
-                let commands: Vec<Command<Message>> = selected_devices
-                    .into_iter()
-                    .map(|(index, ip)| {
-                        let username = username.clone();
-                        let password = password.clone();
-                        let controller_url = controller_url.clone();
-
-                        // Create channel for this device
-                        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-                        self.device_progress_receivers.insert(index, Arc::new(tokio::sync::Mutex::new(rx)));
-
-                        Command::perform(
-                            async move {
-                                tokio::task::spawn_blocking(move || {
-                                    ssh_handler::execute_adoption(
-                                        &ip,
-                                        &username,
-                                        &password,
-                                        &controller_url,
-                                        Some(tx),
-                                    )
-                                })
-                                .await
-                                .unwrap()
-                            },
-                            move |result| Message::DeviceAdoptionComplete(index, result),
-                        )
-                    })
-                    .collect();
-
-                Command::batch(commands)
-            }
-            Message::AdoptSelectedAlt => {
-                // Mark all selected devices as in progress
-                for device in &mut self.devices {
-                    if device.selected {
-                        device.status = DeviceStatus::InProgress;
-                        device.logs = String::new();
-                    }
+            Message::CancelAllJobs => {
+                for index in 0..self.devices.len() {
+                    self.cancel_job(index);
                 }
-
-                // Collect selected devices for parallel adoption
-                let selected_devices: Vec<(usize, String)> = self.devices
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, d)| d.selected)
-                    .map(|(i, d)| (i, d.ip.clone()))
-                    .collect();
-
-                let username = self.config.alt_ssh_username.clone();
-                let password = self.config.alt_ssh_password.clone();
-                let controller_url = self.config.controller_url.clone();
-
-                // Clear old receivers
-                self.device_progress_receivers.clear();
-
-                // Launch parallel adoption tasks with progress channels
-                let commands: Vec<Command<Message>> = selected_devices
-                    .into_iter()
-                    .map(|(index, ip)| {
-                        let username = username.clone();
-                        let password = password.clone();
-                        let controller_url = controller_url.clone();
-
-                        // Create channel for this device
-                        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
-                        self.device_progress_receivers.insert(index, Arc::new(tokio::sync::Mutex::new(rx)));
-
-                        Command::perform(
-                            async move {
-                                tokio::task::spawn_blocking(move || {
-                                    ssh_handler::execute_adoption(
-                                        &ip,
-                                        &username,
-                                        &password,
-                                        &controller_url,
-                                        Some(tx),
-                                    )
-                                })
-                                .await
-                                .unwrap()
-                            },
-                            move |result| Message::DeviceAdoptionComplete(index, result),
-                        )
-                    })
-                    .collect();
-
-                Command::batch(commands)
+                Command::none()
+            }
+            Message::IdentifySelected => {
+                self.enqueue_selected(ssh_handler::identify_device, Message::DeviceIdentifyComplete)
             }
             Message::DeviceAdoptionComplete(index, result) => {
                 if let Some(device) = self.devices.get_mut(index) {
                     match result {
-                        Ok(logs) => {
-                            device.status = DeviceStatus::Success;
-                            device.logs = logs;
+                        Ok(report) => {
+                            device.status = if report.confirmed == Some(false) {
+                                DeviceStatus::Unconfirmed
+                            } else {
+                                DeviceStatus::Success
+                            };
+                            device.logs = report.log;
+                            if report.info.is_some() {
+                                device.info = report.info;
+                            }
                         }
-                        Err(logs) => {
-                            device.status = DeviceStatus::Error;
-                            device.logs = logs;
+                        Err(err) => record_failure(device, err),
+                    }
+                }
+                self.finish_job(index)
+            }
+            Message::DeviceIdentifyComplete(index, result) => {
+                if let Some(device) = self.devices.get_mut(index) {
+                    match result {
+                        Ok(report) => {
+                            // Identified but not adopted yet
+                            device.status = DeviceStatus::Pending;
+                            device.logs = report.log;
+                            device.info = report.info;
+                            // Already connected devices don't need adopting
+                            device.selected = !device.info.as_ref().is_some_and(|i| i.is_connected());
                         }
+                        Err(err) => record_failure(device, err),
+                    }
+                }
+                self.finish_job(index)
+            }
+            Message::TrustHostKey(index) => {
+                if let Some(device) = self.devices.get_mut(index) {
+                    if let Some((address, key)) = device.changed_host_key.take() {
+                        known_hosts::trust(&address, Some(&device.mac), &key);
+                        device.logs.push_str(&format!("\nTrusted new host key {}\n", key));
                     }
                 }
-                // Clean up the receiver for this device
-                self.device_progress_receivers.remove(&index);
                 Command::none()
             }
             Message::DeviceLogUpdate(index, log_chunk) => {
@@ -473,32 +877,51 @@ impl Application for UnifiAdoption {
                 }
                 Command::none()
             }
-            Message::ClosePopup => {
-                Command::none()
-            }
         }
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         if self.show_settings {
             views::settings_view(
                 &self.active_tab,
                 &self.controller_url_input,
-                &self.username_input,
-                &self.password_input,
-                &self.alt_username_input,
-                &self.alt_password_input,
+                &self.ssh_port_input,
+                &self.credentials_input,
+                self.host_key_policy_input,
+                self.verify_adoption_input,
+                &self.verify_timeout_input,
+                self.reinform_input,
+                &self.reinform_interval_input,
+                &self.max_parallel_jobs_input,
+                &self.scan_max_in_flight_input,
+                &self.scan_rate_input,
+                self.scan_ipv6_input,
+                &self.dhcp_lease_file_input,
+                &self.scan_ports_input,
             )
         } else if self.show_scan {
             views::scan_view(
-                &self.ip_range_start,
-                &self.ip_range_end,
+                &self.scan_targets,
+                self.scan_error.as_deref(),
+                &self.interfaces,
+                &self.scan_interface,
                 &self.devices,
                 self.expanded_device_index,
                 self.is_scanning,
+                self.scan_progress,
+                self.listen_cancel.is_some(),
+                self.listen_error.as_deref(),
+                self.inform_cancel.is_some(),
+                inform::port_from_url(&self.config.controller_url),
+                self.inform_error.as_deref(),
             )
         } else {
-            views::main_view(&self.ip_address, &self.log_editor_content, self.is_running)
+            views::main_view(
+                &self.ip_address,
+                &self.log_editor_content,
+                self.is_running,
+                self.manual_changed_host_key.as_ref().map(|(_, key)| key),
+            )
         }
     }
 
@@ -541,6 +964,69 @@ impl Application for UnifiAdoption {
             subscriptions.push(sub);
         }
 
+        // Scan results, delivered in batches as hosts answer
+        if let Some(rx) = &self.scan_event_receiver {
+            let rx = Arc::clone(rx);
+            let sub = iced::subscription::unfold("scan_events", rx, move |rx| async move {
+                let mut events = Vec::new();
+                {
+                    let mut receiver = rx.lock().await;
+                    match receiver.recv().await {
+                        Some(event) => events.push(event),
+                        // Scan finished; ScanComplete drops this subscription
+                        None => std::future::pending::<()>().await,
+                    }
+                    while let Ok(event) = receiver.try_recv() {
+                        events.push(event);
+                    }
+                }
+                (Message::ScanUpdate(events), rx)
+            });
+            subscriptions.push(sub);
+        }
+
+        // Devices heard by the passive listener
+        if let Some(rx) = &self.listen_event_receiver {
+            let rx = Arc::clone(rx);
+            let id = ("listen_events", self.listen_generation);
+            let sub = iced::subscription::unfold(id, rx, move |rx| async move {
+                let mut events = Vec::new();
+                {
+                    let mut receiver = rx.lock().await;
+                    match receiver.recv().await {
+                        Some(event) => events.push(event),
+                        None => std::future::pending::<()>().await,
+                    }
+                    while let Ok(event) = receiver.try_recv() {
+                        events.push(event);
+                    }
+                }
+                (Message::ScanUpdate(events), rx)
+            });
+            subscriptions.push(sub);
+        }
+
+        // Devices posting to the inform endpoint
+        if let Some(rx) = &self.inform_event_receiver {
+            let rx = Arc::clone(rx);
+            let id = ("inform_events", self.inform_generation);
+            let sub = iced::subscription::unfold(id, rx, move |rx| async move {
+                let mut informs = Vec::new();
+                {
+                    let mut receiver = rx.lock().await;
+                    match receiver.recv().await {
+                        Some(received) => informs.push(received),
+                        None => std::future::pending::<()>().await,
+                    }
+                    while let Ok(received) = receiver.try_recv() {
+                        informs.push(received);
+                    }
+                }
+                (Message::InformUpdate(informs), rx)
+            });
+            subscriptions.push(sub);
+        }
+
         // Device adoption subscriptions (bulk adoption)
         for (index, rx) in &self.device_progress_receivers {
             let rx = Arc::clone(rx);
@@ -1,37 +1,90 @@
-use crate::models::SettingsTab;
 use iced::widget::text_editor;
+use unifi_adopt::config::HostKeyPolicy;
+use unifi_adopt::inform::InformReceived;
+use unifi_adopt::network_interface::NetworkInterface;
+use unifi_adopt::{AdoptionError, AdoptionReport, ScanEvent};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingsTab {
+    General,
+    Credentials,
+}
+
+/// Which interface the scan sends its probes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterfaceChoice {
+    /// Let the routing table decide.
+    Automatic,
+    Interface(NetworkInterface),
+}
+
+impl std::fmt::Display for InterfaceChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InterfaceChoice::Automatic => write!(f, "Automatic"),
+            InterfaceChoice::Interface(net) => write!(f, "{}", net),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum Message {
     IpAddressChanged(String),
     AdoptClicked,
-    AdoptAgainClicked,
     SettingsClicked,
     CloseSettings,
     SaveSettings,
     TabSelected(SettingsTab),
     ControllerUrlChanged(String),
-    UsernameChanged(String),
-    PasswordChanged(String),
-    AltUsernameChanged(String),
-    AltPasswordChanged(String),
-    AdoptionComplete(Result<String, String>),
+    SshPortChanged(String),
+    HostKeyPolicySelected(HostKeyPolicy),
+    VerifyAdoptionToggled(bool),
+    VerifyTimeoutChanged(String),
+    ReinformToggled(bool),
+    ReinformIntervalChanged(String),
+    MaxParallelJobsChanged(String),
+    ScanMaxInFlightChanged(String),
+    ScanRateChanged(String),
+    ScanIpv6Toggled(bool),
+    DhcpLeaseFileChanged(String),
+    ScanPortsChanged(String),
+    CredentialNameChanged(usize, String),
+    CredentialUsernameChanged(usize, String),
+    CredentialPasswordChanged(usize, String),
+    CredentialKeyPathChanged(usize, String),
+    CredentialKeyPassphraseChanged(usize, String),
+    CredentialUseAgentToggled(usize, bool),
+    CredentialPortChanged(usize, String),
+    AddCredential,
+    RemoveCredential(usize),
+    MoveCredentialUp(usize),
+    MoveCredentialDown(usize),
+    AdoptionComplete(Result<AdoptionReport, AdoptionError>),
+    CancelAdoption,
+    TrustManualHostKey,
     LogUpdate(String),
     LogEditorAction(text_editor::Action),
-    ScanClicked,
-    CloseScan,
     ManualEntryClicked,
     CloseManualEntry,
-    IpRangeStartChanged(String),
-    IpRangeEndChanged(String),
+    ScanTargetsChanged(String),
+    InterfaceSelected(InterfaceChoice),
     ScanDevices,
-    ScanComplete(Result<Vec<crate::models::Device>, String>),
+    CancelScan,
+    ScanUpdate(Vec<ScanEvent>),
+    ScanComplete(Result<Vec<unifi_adopt::Device>, String>),
+    ListenToggled(bool),
+    ListenStopped(Result<(), String>),
+    InformToggled(bool),
+    InformStopped(Result<(), String>),
+    InformUpdate(Vec<InformReceived>),
     DeviceToggled(usize, bool),
     DeviceRowClicked(usize),
-    DeviceStatusIconClicked(usize),
-    AdoptSelectedDefault,
-    AdoptSelectedAlt,
-    DeviceAdoptionComplete(usize, Result<String, String>),
+    AdoptSelected,
+    CancelDevice(usize),
+    CancelAllJobs,
+    IdentifySelected,
+    DeviceAdoptionComplete(usize, Result<AdoptionReport, AdoptionError>),
+    DeviceIdentifyComplete(usize, Result<AdoptionReport, AdoptionError>),
+    TrustHostKey(usize),
     DeviceLogUpdate(usize, String),
-    ClosePopup,
 }
@@ -1,18 +1,48 @@
-#[derive(Debug, Clone, PartialEq)]
-pub enum SettingsTab {
-    General,
-    DefaultCredentials,
-    AlternativeCredentials,
-}
+use crate::device_info::DeviceInfo;
+use crate::discovery::DiscoveryInfo;
+use crate::fingerprint::OpenPort;
+use crate::known_hosts::HostKey;
+use std::net::IpAddr;
+use std::time::Duration;
 
+/// Adoption state of a discovered device.
 #[derive(Debug, Clone, PartialEq)]
 pub enum DeviceStatus {
     Pending,
+    /// Waiting in the job queue for a free slot.
+    Queued,
+    /// Running: an SSH session to the device is open.
     InProgress,
     Success,
+    /// `set-inform` was sent but the device never reported reaching the
+    /// controller before verification timed out.
+    Unconfirmed,
     Error,
+    /// Stopped by the user before it finished.
+    Cancelled,
+}
+
+/// What sort of gear a scanned device looks like, from [`Device::kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    /// An access point, switch or gateway that can be adopted over SSH.
+    UnifiDevice,
+    /// A UniFi OS console (Dream Machine, Cloud Key) serving its own web UI.
+    UnifiConsole,
+    Other,
 }
 
+impl DeviceKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeviceKind::UnifiDevice => "UniFi device",
+            DeviceKind::UnifiConsole => "UniFi OS console",
+            DeviceKind::Other => "",
+        }
+    }
+}
+
+/// A host found by [`crate::network_scanner::scan_network`].
 #[derive(Debug, Clone)]
 pub struct Device {
     pub ip: String,
@@ -22,4 +52,77 @@ pub struct Device {
     pub status: DeviceStatus,
     pub logs: String,
     pub has_ssh: bool,
+    /// TCP ports from the scan's port profile that accepted a connection,
+    /// with the SSH or HTTP server banner each one gave.
+    pub ports: Vec<OpenPort>,
+    /// Name from the DHCP lease file, the device's discovery reply, reverse
+    /// DNS or mDNS, in that order of preference.
+    pub hostname: Option<String>,
+    /// Ping round-trip time from the scan.
+    pub rtt: Option<Duration>,
+    /// What the device said about itself in reply to a UniFi discovery
+    /// probe during the scan.
+    pub discovery: Option<DiscoveryInfo>,
+    /// Model, firmware and inform status from the device's `info` command,
+    /// once it has been identified or adopted.
+    pub info: Option<DeviceInfo>,
+    /// Host key presented on the last adoption attempt when it did not match
+    /// known_hosts, with the address it was checked under, kept so the user
+    /// can choose to trust it.
+    pub changed_host_key: Option<(String, HostKey)>,
+    /// The device has posted to the built-in inform endpoint since it was
+    /// found.
+    pub inform_received: bool,
+}
+
+impl Device {
+    /// Orders devices by address: IPv4 numerically, then IPv6. A link-local
+    /// scope (`%eth0`) is ignored.
+    pub fn sort_key(&self) -> (u8, u128) {
+        let ip = self.ip.split('%').next().unwrap_or_default();
+        match ip.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => (0, u32::from(ip) as u128),
+            Ok(IpAddr::V6(ip)) => (1, u128::from(ip)),
+            Err(_) => (2, 0),
+        }
+    }
+
+    /// Ubiquiti gear (by discovery reply or MAC vendor) that serves HTTPS on
+    /// 443 or 8443 is taken for a UniFi OS console; the rest of it for an
+    /// adoptable device.
+    pub fn kind(&self) -> DeviceKind {
+        if self.discovery.is_none() && !self.company.contains("Ubiquiti") {
+            return DeviceKind::Other;
+        }
+        if self.ports.iter().any(|p| matches!(p.port, 443 | 8443)) {
+            DeviceKind::UnifiConsole
+        } else {
+            DeviceKind::UnifiDevice
+        }
+    }
+
+    /// The first port from the scan that speaks SSH.
+    pub fn ssh_port(&self) -> Option<u16> {
+        self.ports.iter().find(|p| p.is_ssh()).map(|p| p.port)
+    }
+
+    /// The address to adopt the device at: its IP, with the SSH port the
+    /// scan found, even 22, so it wins over the credential set's or the
+    /// configured port. Without one, adoption falls back to those.
+    pub fn ssh_target(&self) -> String {
+        match self.ssh_port() {
+            Some(port) => crate::network_interface::host_with_port(&self.ip, port),
+            None => self.ip.clone(),
+        }
+    }
+
+    /// The SSH identification line of the first port that speaks SSH.
+    pub fn ssh_banner(&self) -> Option<&str> {
+        self.ports.iter().find(|p| p.is_ssh()).and_then(|p| p.banner.as_deref())
+    }
+
+    /// The HTTP `Server` header of the first web port that sent one.
+    pub fn http_server(&self) -> Option<&str> {
+        self.ports.iter().find(|p| p.is_http()).and_then(|p| p.banner.as_deref())
+    }
 }
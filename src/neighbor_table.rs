@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// An entry in the IPv6 neighbor table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv6Neighbor {
+    pub ip: Ipv6Addr,
+    /// Interface name (`eth0`, `en0`), or its index on Windows.
+    pub interface: String,
+    /// Uppercase `AA:BB:CC:DD:EE:FF`.
+    pub mac: String,
+}
+
+/// Read the OS neighbor (ARP) table into a map of uppercase
+/// `AA:BB:CC:DD:EE:FF` MACs keyed by address. Incomplete entries are left
+/// out. Returns an empty map if the table can't be read.
+///
+/// Linux reads `/proc/net/arp`; macOS and Windows run `arp -a` once.
+pub async fn load() -> HashMap<Ipv4Addr, String> {
+    #[cfg(target_os = "linux")]
+    {
+        tokio::fs::read_to_string("/proc/net/arp")
+            .await
+            .map(|contents| parse_proc_net_arp(&contents))
+            .unwrap_or_default()
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        match tokio::process::Command::new("arp").arg("-an").output().await {
+            Ok(output) => parse_bsd_arp(&String::from_utf8_lossy(&output.stdout)),
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let mut cmd = tokio::process::Command::new("arp");
+        cmd.arg("-a");
+
+        // Hide console window on Windows
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+
+        match cmd.output().await {
+            Ok(output) => parse_windows_arp(&String::from_utf8_lossy(&output.stdout)),
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        HashMap::new()
+    }
+}
+
+/// Read the OS IPv6 neighbor table. Incomplete and multicast entries are
+/// left out. Returns an empty list if the table can't be read.
+///
+/// Runs `ip -6 neigh` on Linux, `ndp -an` on macOS and
+/// `netsh interface ipv6 show neighbors` on Windows.
+pub async fn load_ipv6() -> Vec<Ipv6Neighbor> {
+    #[cfg(target_os = "linux")]
+    {
+        match tokio::process::Command::new("ip").args(["-6", "neigh", "show"]).output().await {
+            Ok(output) => parse_ip_neigh(&String::from_utf8_lossy(&output.stdout)),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        match tokio::process::Command::new("ndp").arg("-an").output().await {
+            Ok(output) => parse_ndp(&String::from_utf8_lossy(&output.stdout)),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        let mut cmd = tokio::process::Command::new("netsh");
+        cmd.args(["interface", "ipv6", "show", "neighbors"]);
+
+        // Hide console window on Windows
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        use std::os::windows::process::CommandExt;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+
+        match cmd.output().await {
+            Ok(output) => parse_netsh_neighbors(&String::from_utf8_lossy(&output.stdout)),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        Vec::new()
+    }
+}
+
+// Linux `ip -6 neigh show`:
+// fe80::1 dev eth0 lladdr aa:bb:cc:dd:ee:ff router REACHABLE
+// fd00::7 dev eth0 FAILED
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_ip_neigh(output: &str) -> Vec<Ipv6Neighbor> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let field = |name: &str| {
+                let pos = parts.iter().position(|part| *part == name)?;
+                parts.get(pos + 1).copied()
+            };
+            ipv6_neighbor(parts.first()?, field("dev")?, field("lladdr")?)
+        })
+        .collect()
+}
+
+// macOS `ndp -an`:
+// Neighbor                        Linklayer Address  Netif Expire    St Flgs Prbs
+// fe80::1%en0                     0:11:22:33:44:55     en0 23h59m58s S  R
+// 2001:db8::7                     (incomplete)         en0 expired   N
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_ndp(output: &str) -> Vec<Ipv6Neighbor> {
+    output
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 3 {
+                return None;
+            }
+            let ip = parts[0].split('%').next()?;
+            ipv6_neighbor(ip, parts[2], parts[1])
+        })
+        .collect()
+}
+
+// Windows `netsh interface ipv6 show neighbors`:
+// Interface 12: Ethernet
+//
+// Internet Address                              Physical Address   Type
+// --------------------------------------------  -----------------  -----------
+// fe80::1                                       00-11-22-33-44-55  Reachable (Router)
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn parse_netsh_neighbors(output: &str) -> Vec<Ipv6Neighbor> {
+    let mut interface = String::new();
+    let mut neighbors = Vec::new();
+    for line in output.lines() {
+        if let Some(rest) = line.trim().strip_prefix("Interface ") {
+            interface = rest.split(':').next().unwrap_or_default().trim().to_string();
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() >= 2 {
+            neighbors.extend(ipv6_neighbor(parts[0], &interface, parts[1]));
+        }
+    }
+    neighbors
+}
+
+fn ipv6_neighbor(ip: &str, interface: &str, mac: &str) -> Option<Ipv6Neighbor> {
+    let ip: Ipv6Addr = ip.parse().ok()?;
+    if ip.is_multicast() || interface.is_empty() {
+        return None;
+    }
+    Some(Ipv6Neighbor {
+        ip,
+        interface: interface.to_string(),
+        mac: normalize_mac(mac)?,
+    })
+}
+
+// Linux `/proc/net/arp`:
+// IP address       HW type     Flags       HW address            Mask     Device
+// 192.168.1.1      0x1         0x2         aa:bb:cc:dd:ee:ff     *        eth0
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_proc_net_arp(contents: &str) -> HashMap<Ipv4Addr, String> {
+    contents
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            // Flags 0x0 is an incomplete entry (no reply yet)
+            if parts.len() < 4 || parts[2] == "0x0" {
+                return None;
+            }
+            Some((parts[0].parse().ok()?, normalize_mac(parts[3])?))
+        })
+        .collect()
+}
+
+// macOS `arp -an`:
+// ? (192.168.1.1) at aa:bb:cc:dd:ee:ff on en0 ifscope [ethernet]
+// ? (192.168.1.7) at (incomplete) on en0 ifscope [ethernet]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_bsd_arp(output: &str) -> HashMap<Ipv4Addr, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let start = line.find('(')? + 1;
+            let end = start + line[start..].find(')')?;
+            let ip = line[start..end].parse().ok()?;
+            let mac = line[end..].split_whitespace().nth(2)?;
+            Some((ip, normalize_mac(mac)?))
+        })
+        .collect()
+}
+
+// Windows `arp -a`:
+// Interface: 192.168.1.50 --- 0xb
+//   Internet Address      Physical Address      Type
+//   192.168.1.1           aa-bb-cc-dd-ee-ff     dynamic
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+fn parse_windows_arp(output: &str) -> HashMap<Ipv4Addr, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() < 3 {
+                return None;
+            }
+            Some((parts[0].parse().ok()?, normalize_mac(parts[1])?))
+        })
+        .collect()
+}
+
+// Uppercase, colon-separated and zero-padded (macOS prints `0:1b:...`).
+// Anything that isn't six octets, or is all zeros, is rejected.
+fn normalize_mac(mac: &str) -> Option<String> {
+    let octets: Vec<u8> = mac
+        .split([':', '-'])
+        .map(|part| u8::from_str_radix(part, 16).ok())
+        .collect::<Option<_>>()?;
+    // Broadcast and multicast (group) addresses aren't a device's own
+    if octets.len() != 6 || octets.iter().all(|&b| b == 0) || octets[0] & 0x01 != 0 {
+        return None;
+    }
+    Some(
+        octets
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> Ipv4Addr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn proc_net_arp() {
+        let sample = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.10     0x1         0x2         78:8a:20:aa:bb:cc     *        eth0
+192.168.1.1      0x1         0x2         f0:9f:c2:11:22:33     *        eth0
+192.168.1.23     0x1         0x0         00:00:00:00:00:00     *        eth0
+";
+        let table = parse_proc_net_arp(sample);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[&ip("192.168.1.1")], "F0:9F:C2:11:22:33");
+        assert_eq!(table[&ip("192.168.1.10")], "78:8A:20:AA:BB:CC");
+        assert!(!table.contains_key(&ip("192.168.1.23")));
+    }
+
+    #[test]
+    fn bsd_arp() {
+        let sample = "\
+? (192.168.1.1) at f0:9f:c2:11:22:33 on en0 ifscope [ethernet]
+? (192.168.1.10) at 78:8a:20:a:b:c on en0 ifscope [ethernet]
+? (192.168.1.7) at (incomplete) on en0 ifscope [ethernet]
+? (224.0.0.251) at 1:0:5e:0:0:fb on en0 ifscope permanent [ethernet]
+";
+        let table = parse_bsd_arp(sample);
+        assert_eq!(table[&ip("192.168.1.1")], "F0:9F:C2:11:22:33");
+        assert_eq!(table[&ip("192.168.1.10")], "78:8A:20:0A:0B:0C");
+        assert!(!table.contains_key(&ip("224.0.0.251")));
+        assert!(!table.contains_key(&ip("192.168.1.7")));
+    }
+
+    #[test]
+    fn windows_arp() {
+        let sample = "\r
+Interface: 192.168.1.50 --- 0xb\r
+  Internet Address      Physical Address      Type\r
+  192.168.1.1           f0-9f-c2-11-22-33     dynamic   \r
+  192.168.1.10          78-8a-20-aa-bb-cc     dynamic   \r
+  192.168.1.255         ff-ff-ff-ff-ff-ff     static    \r
+\r
+Interface: 10.0.0.5 --- 0x11\r
+  Internet Address      Physical Address      Type\r
+  10.0.0.1              00-11-22-33-44-55     dynamic   \r
+";
+        let table = parse_windows_arp(sample);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[&ip("192.168.1.1")], "F0:9F:C2:11:22:33");
+        assert_eq!(table[&ip("192.168.1.10")], "78:8A:20:AA:BB:CC");
+        assert_eq!(table[&ip("10.0.0.1")], "00:11:22:33:44:55");
+        assert!(!table.contains_key(&ip("192.168.1.50")));
+        assert!(!table.contains_key(&ip("192.168.1.255")));
+    }
+
+    #[test]
+    fn ip_neigh() {
+        let sample = "\
+fe80::1 dev eth0 lladdr f0:9f:c2:11:22:33 router REACHABLE
+fd00::20 dev eth0 lladdr 78:8a:20:aa:bb:cc STALE
+fd00::7 dev eth0 FAILED
+fe80::9 dev wlan0 INCOMPLETE
+";
+        let table = parse_ip_neigh(sample);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0].ip, "fe80::1".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(table[0].interface, "eth0");
+        assert_eq!(table[0].mac, "F0:9F:C2:11:22:33");
+        assert_eq!(table[1].mac, "78:8A:20:AA:BB:CC");
+    }
+
+    #[test]
+    fn ndp() {
+        let sample = "\
+Neighbor                        Linklayer Address  Netif Expire    St Flgs Prbs
+fe80::1%en0                     f0:9f:c2:11:22:33    en0 23h59m58s S  R
+fd00::20                        78:8a:20:a:b:c       en0 23h59m58s S
+fd00::7                         (incomplete)         en0 expired   N
+";
+        let table = parse_ndp(sample);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0].ip, "fe80::1".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(table[0].interface, "en0");
+        assert_eq!(table[1].mac, "78:8A:20:0A:0B:0C");
+    }
+
+    #[test]
+    fn netsh_neighbors() {
+        let sample = "\r
+Interface 12: Ethernet\r
+\r
+\r
+Internet Address                              Physical Address   Type\r
+--------------------------------------------  -----------------  -----------\r
+fe80::1                                       f0-9f-c2-11-22-33  Reachable (Router)\r
+fd00::7                                       00-00-00-00-00-00  Unreachable\r
+ff02::1                                       33-33-00-00-00-01  Permanent\r
+";
+        let table = parse_netsh_neighbors(sample);
+        assert_eq!(table.len(), 1);
+        assert_eq!(table[0].interface, "12");
+        assert_eq!(table[0].mac, "F0:9F:C2:11:22:33");
+    }
+
+    #[test]
+    fn keys_are_exact_addresses() {
+        // 192.168.1.1 must not pick up the entry for 192.168.1.10
+        let sample = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.10     0x1         0x2         78:8a:20:aa:bb:cc     *        eth0
+";
+        let table = parse_proc_net_arp(sample);
+        assert!(!table.contains_key(&ip("192.168.1.1")));
+    }
+}
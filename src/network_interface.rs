@@ -1,5 +1,5 @@
 use get_if_addrs::{get_if_addrs, IfAddr};
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct NetworkInterface {
@@ -10,12 +10,152 @@ pub struct NetworkInterface {
     pub cidr: String,
 }
 
+impl NetworkInterface {
+    /// Whether `ip` is inside this interface's subnet.
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        self.network_and_mask()
+            .is_some_and(|(network, mask)| u32::from(ip) & mask == network)
+    }
+
+    /// The subnet's broadcast address.
+    pub fn broadcast(&self) -> Option<Ipv4Addr> {
+        let (network, mask) = self.network_and_mask()?;
+        Some(Ipv4Addr::from(network | !mask))
+    }
+
+    fn network_and_mask(&self) -> Option<(u32, u32)> {
+        let (network, bits) = self.cidr.split_once('/')?;
+        let network: Ipv4Addr = network.parse().ok()?;
+        let bits: u32 = bits.parse().ok()?;
+        let mask = u32::MAX.checked_shl(32 - bits).unwrap_or(0);
+        Some((u32::from(network), mask))
+    }
+}
+
 impl std::fmt::Display for NetworkInterface {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{} - {} ({})", self.name, self.ip, self.cidr)
     }
 }
 
+/// An IPv6 address on a local interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ipv6Interface {
+    pub name: String,
+    pub ip: Ipv6Addr,
+    pub prefix_len: u32,
+}
+
+/// List every non-loopback IPv6 address, link-local ones included.
+pub fn get_local_ipv6_networks() -> Vec<Ipv6Interface> {
+    let Ok(interfaces) = get_if_addrs() else {
+        return Vec::new();
+    };
+    interfaces
+        .into_iter()
+        .filter_map(|iface| match iface.addr {
+            IfAddr::V6(addr) if !addr.ip.is_loopback() => Some(Ipv6Interface {
+                name: iface.name,
+                ip: addr.ip,
+                prefix_len: u128::from(addr.netmask).count_ones(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The OS index of the interface called `name`, which IPv6 link-local
+/// and multicast addresses need as their scope. Always `None` on Windows,
+/// where interfaces are only known by index.
+pub fn interface_index(name: &str) -> Option<u32> {
+    #[cfg(unix)]
+    {
+        let name = std::ffi::CString::new(name).ok()?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        (index != 0).then_some(index)
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = name;
+        None
+    }
+}
+
+/// Turn a device address as shown in the scan table into something to
+/// connect to: `192.168.1.20`, `fd00::20`, `[fd00::20]` or a link-local
+/// `fe80::1%eth0` (the scope may also be a numeric interface index).
+pub fn socket_addr(host: &str, port: u16) -> Result<SocketAddr, String> {
+    let host = host.trim().trim_start_matches('[').trim_end_matches(']');
+    let invalid = || format!("Invalid IP address: {}", host);
+
+    if !host.contains(':') {
+        let ip: Ipv4Addr = host.parse().map_err(|_| invalid())?;
+        return Ok(SocketAddr::new(IpAddr::V4(ip), port));
+    }
+
+    let (ip, scope) = match host.split_once('%') {
+        Some((ip, scope)) => {
+            let scope = scope
+                .parse()
+                .ok()
+                .or_else(|| interface_index(scope))
+                .ok_or_else(|| format!("Unknown interface: {}", scope))?;
+            (ip, scope)
+        }
+        None => (host, 0),
+    };
+    let ip: Ipv6Addr = ip.parse().map_err(|_| invalid())?;
+    Ok(SocketAddr::V6(SocketAddrV6::new(ip, port, 0, scope)))
+}
+
+/// Split a device address into the host and, when it names one, the port:
+/// `192.168.1.20:2222`, `[fd00::20]:2222`. Bare IPv6 addresses (with or
+/// without a scope) have no port.
+pub fn split_host_port(address: &str) -> Result<(&str, Option<u16>), String> {
+    let address = address.trim();
+    let parse_port = |port: &str| match port.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(Some(port)),
+        _ => Err(format!("Invalid port: {}", port)),
+    };
+
+    if let Some(rest) = address.strip_prefix('[') {
+        let (host, after) = rest
+            .split_once(']')
+            .ok_or_else(|| format!("Invalid IP address: {}", address))?;
+        return match after.strip_prefix(':') {
+            Some(port) => Ok((host, parse_port(port)?)),
+            None if after.is_empty() => Ok((host, None)),
+            None => Err(format!("Invalid IP address: {}", address)),
+        };
+    }
+    match address.split_once(':') {
+        Some((host, port)) if !port.contains(':') => Ok((host, parse_port(port)?)),
+        _ => Ok((address, None)),
+    }
+}
+
+/// Format `host` and `port` the way [`split_host_port`] reads them, leaving
+/// the port off when it is 22.
+pub fn join_host_port(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        host_with_port(host, port)
+    }
+}
+
+/// Like [`join_host_port`], but always keeps the port, so a configured
+/// default port can't replace it.
+pub fn host_with_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// List every non-loopback IPv4 interface with its subnet range.
 pub fn get_local_networks() -> Vec<NetworkInterface> {
     let mut networks = Vec::new();
 
@@ -71,6 +211,12 @@ fn calculate_network_range(ip: Ipv4Addr, netmask: Ipv4Addr) -> (String, String,
     (start_ip, end_ip, cidr)
 }
 
+/// The local interface whose subnet contains `ip`, if any.
+pub fn interface_for(ip: Ipv4Addr) -> Option<NetworkInterface> {
+    get_local_networks().into_iter().find(|net| net.contains(ip))
+}
+
+/// Pick the interface most likely to be the LAN the devices are on.
 pub fn get_default_network() -> Option<NetworkInterface> {
     let networks = get_local_networks();
 
@@ -107,3 +253,30 @@ pub fn get_default_network() -> Option<NetworkInterface> {
     // Return first available network
     networks.first().cloned()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_and_port() {
+        assert_eq!(split_host_port("192.168.1.20").unwrap(), ("192.168.1.20", None));
+        assert_eq!(split_host_port("192.168.1.20:2222").unwrap(), ("192.168.1.20", Some(2222)));
+        assert_eq!(split_host_port("fe80::1%eth0").unwrap(), ("fe80::1%eth0", None));
+        assert_eq!(split_host_port("[fd00::20]").unwrap(), ("fd00::20", None));
+        assert_eq!(split_host_port("[fe80::1%eth0]:2222").unwrap(), ("fe80::1%eth0", Some(2222)));
+        assert!(split_host_port("192.168.1.20:ssh").is_err());
+        assert!(split_host_port("[fd00::20]2222").is_err());
+    }
+
+    #[test]
+    fn join_round_trips() {
+        assert_eq!(join_host_port("192.168.1.20", 22), "192.168.1.20");
+        assert_eq!(join_host_port("192.168.1.20", 2222), "192.168.1.20:2222");
+        assert_eq!(join_host_port("fe80::1%eth0", 2222), "[fe80::1%eth0]:2222");
+        assert_eq!(host_with_port("192.168.1.20", 22), "192.168.1.20:22");
+        assert_eq!(host_with_port("fd00::20", 22), "[fd00::20]:22");
+        let joined = join_host_port("fd00::20", 2222);
+        assert_eq!(split_host_port(&joined).unwrap(), ("fd00::20", Some(2222)));
+    }
+}
@@ -1,37 +1,656 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::Duration;
+use crate::cancel::CancelToken;
+use crate::config::AppConfig;
+use crate::dhcp_leases::Leases;
+use crate::discovery::{self, DiscoveryInfo};
+use crate::fingerprint::{self, OpenPort};
+use crate::rate_limit::RateLimiter;
+use crate::{arp, hostname, neighbor_table, network_interface};
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use crate::models::Device;
 use crate::models::DeviceStatus;
 
-pub async fn scan_network(start_ip: &str, end_ip: &str) -> Result<Vec<Device>, String> {
-    let start = parse_ip(start_ip)?;
-    let end = parse_ip(end_ip)?;
+/// Progress reported by [`scan_network`] while it runs.
+#[derive(Debug, Clone)]
+pub enum ScanEvent {
+    /// A host answered.
+    Found(Box<Device>),
+    /// `probed` of the `total` addresses in the range have been checked.
+    Progress { probed: usize, total: usize },
+}
+
+/// Limits on how hard [`scan_network`] hits the network.
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Most hosts being checked at once, by the scan and by the IPv6 and
+    /// announcement checks alike.
+    pub max_in_flight: usize,
+    /// Most packets sent per second across the whole scan: pings, discovery
+    /// and port probes, name lookups and ARP requests all share it. `None`
+    /// sends them as fast as checks free up.
+    pub probes_per_second: Option<u32>,
+    /// Local address to send probes from, to pin the scan to one interface
+    /// (say, the LAN rather than a VPN). `None` leaves it to the routing
+    /// table.
+    pub source_ip: Option<Ipv4Addr>,
+    /// After the IPv4 targets, look for IPv6 hosts on the same links.
+    pub ipv6: bool,
+    /// DHCP lease file to take hostnames from, read once at the start of
+    /// the scan.
+    pub dhcp_lease_file: Option<std::path::PathBuf>,
+    /// TCP ports to try on each live host, with banner grabbing. Including
+    /// 10001 sends the UniFi discovery probe as well. A host has SSH if any
+    /// of them sends an SSH banner (or 22 accepts without one).
+    pub ports: Vec<u16>,
+}
+
+impl ScanOptions {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            max_in_flight: config.scan_max_in_flight.max(1),
+            probes_per_second: Some(config.scan_probes_per_second).filter(|&pps| pps > 0),
+            source_ip: None,
+            ipv6: config.scan_ipv6,
+            dhcp_lease_file: Some(config.dhcp_lease_file.trim())
+                .filter(|path| !path.is_empty())
+                .map(std::path::PathBuf::from),
+            // SSH on the configured port should always be noticed
+            ports: config
+                .scan_ports
+                .iter()
+                .copied()
+                .chain(std::iter::once(config.ssh_port))
+                .collect::<std::collections::BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+/// The addresses a scan covers, parsed from a comma-separated list of:
+///
+/// - single hosts: `192.168.1.20`
+/// - ranges: `192.168.1.10-192.168.1.50`, or `192.168.1.10-50` within the
+///   last octet
+/// - CIDR blocks: `10.0.8.0/22` (without the network and broadcast
+///   addresses, except for /31 and /32)
+/// - exclusions of any of the above, prefixed with `!`: `!10.0.8.1`
+///
+/// Stored as sorted, non-overlapping ranges, so the same address is never
+/// probed twice. The default set is empty, for IPv6-only scans.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TargetSet {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl TargetSet {
+    /// Largest number of addresses a scan will take on (a /16).
+    pub const MAX_ADDRESSES: u64 = 65_536;
+
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item.strip_prefix('!') {
+                Some(excluded) => exclude.push(parse_target(excluded.trim())?),
+                None => include.push(parse_target(item)?),
+            }
+        }
+        if include.is_empty() {
+            return Err(String::from("Enter an address, range or CIDR block to scan"));
+        }
+
+        let mut ranges = merge_ranges(include);
+        for (start, end) in merge_ranges(exclude) {
+            ranges = ranges
+                .into_iter()
+                .flat_map(|(from, to)| {
+                    let before = (from < start).then(|| (from, to.min(start - 1)));
+                    let after = (to > end).then(|| (from.max(end + 1), to));
+                    before.into_iter().chain(after)
+                })
+                .collect();
+        }
+
+        let targets = Self { ranges };
+        if targets.is_empty() {
+            return Err(String::from("Nothing left to scan after the exclusions"));
+        }
+        if targets.len() > Self::MAX_ADDRESSES {
+            return Err(format!(
+                "{} addresses is too many to scan, the limit is {}",
+                targets.len(),
+                Self::MAX_ADDRESSES
+            ));
+        }
+        Ok(targets)
+    }
+
+    /// Number of addresses in the set.
+    pub fn len(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|&(start, end)| (end - start) as u64 + 1)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        let ip = u32::from(ip);
+        self.ranges.iter().any(|&(start, end)| (start..=end).contains(&ip))
+    }
+
+    /// Every address in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = Ipv4Addr> + '_ {
+        self.ranges
+            .iter()
+            .flat_map(|&(start, end)| (start..=end).map(Ipv4Addr::from))
+    }
+}
+
+impl std::str::FromStr for TargetSet {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, String> {
+        Self::parse(spec)
+    }
+}
+
+impl std::fmt::Display for TargetSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = self
+            .ranges
+            .iter()
+            .map(|&(start, end)| {
+                if start == end {
+                    Ipv4Addr::from(start).to_string()
+                } else {
+                    format!("{}-{}", Ipv4Addr::from(start), Ipv4Addr::from(end))
+                }
+            })
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+// One host, range or CIDR block as an inclusive range
+fn parse_target(item: &str) -> Result<(u32, u32), String> {
+    if let Some((network, bits)) = item.split_once('/') {
+        let network = parse_ip(network.trim())?;
+        let bits: u32 = bits
+            .trim()
+            .parse()
+            .ok()
+            .filter(|bits| *bits <= 32)
+            .ok_or_else(|| format!("Invalid CIDR prefix: /{}", bits.trim()))?;
+        let mask = u32::MAX.checked_shl(32 - bits).unwrap_or(0);
+        let (first, last) = (network & mask, network | !mask);
+        return Ok(if bits <= 30 { (first + 1, last - 1) } else { (first, last) });
+    }
+
+    if let Some((start, end)) = item.split_once('-') {
+        let start = parse_ip(start.trim())?;
+        let end = end.trim();
+        // `10.0.0.10-50` ends at 10.0.0.50
+        let end = if end.contains('.') {
+            parse_ip(end)?
+        } else {
+            let octet: u8 = end.parse().map_err(|_| format!("Invalid IP octet: {}", end))?;
+            (start & 0xFFFF_FF00) | octet as u32
+        };
+        if end < start {
+            return Err(format!("Range {} ends before it starts", item));
+        }
+        return Ok((start, end));
+    }
+
+    let ip = parse_ip(item)?;
+    Ok((ip, ip))
+}
+
+// Sort and coalesce overlapping or adjacent ranges
+fn merge_ranges(mut ranges: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    ranges.sort_unstable();
+    let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
 
+/// Ping every address in `targets` and return the hosts that answered, with
+/// their MAC, vendor and SSH availability, sorted by address. At most
+/// `options.max_in_flight` addresses are probed at once, and all the
+/// scan's packets together are paced to `options.probes_per_second`.
+///
+/// With `options.ipv6`, hosts that answer a ping to ff02::1 or are in the
+/// IPv6 neighbor table are added afterwards, from the links the targets are
+/// on (or the source interface, or every link when neither applies).
+/// Link-local addresses carry their scope, e.g. `fe80::1%eth0`. A device
+/// already found over IPv4 isn't listed again.
+///
+/// When `events` is given, each host is also sent as soon as it is found,
+/// followed by a progress count after every probe.
+///
+/// Cancelling `cancel` aborts the outstanding probes (killing their ping
+/// processes) and returns the hosts found so far.
+pub async fn scan_network(
+    targets: &TargetSet,
+    options: &ScanOptions,
+    cancel: &CancelToken,
+    events: Option<tokio::sync::mpsc::UnboundedSender<ScanEvent>>,
+) -> Result<Vec<Device>, String> {
+    let leases = match &options.dhcp_lease_file {
+        Some(path) => Leases::load(path).await?,
+        None => Leases::default(),
+    };
     let mut devices = Vec::new();
-    let mut handles = Vec::new();
-
-    for ip_num in start..=end {
-        let ip = format!("{}.{}.{}.{}",
-            (ip_num >> 24) & 0xFF,
-            (ip_num >> 16) & 0xFF,
-            (ip_num >> 8) & 0xFF,
-            ip_num & 0xFF
-        );
-
-        let handle = tokio::spawn(async move {
-            check_device(ip).await
-        });
-        handles.push(handle);
+    let mut probes = tokio::task::JoinSet::new();
+    let context = Arc::new(ProbeContext::new(options, leases).await);
+    let limiter = &context.limiter;
+
+    // Hosts that answered ARP or a broadcast discovery probe count as alive
+    // even if they drop ping. Both run on each local subnet the targets touch
+    // (only the source interface's, when one is set), alongside the probes
+    // so results still stream in while a long sweep is going.
+    let source = options.source_ip;
+    let local_networks = network_interface::get_local_networks();
+    let interfaces: Vec<_> = local_networks
+        .iter()
+        .filter(|net| source.is_none_or(|source| net.ip == source.to_string()))
+        .filter(|net| targets.iter().any(|ip| net.contains(ip)))
+        .cloned()
+        .collect();
+    let ipv6_links = ipv6_links(&local_networks, &interfaces, source);
+    let send_discovery = options.ports.contains(&discovery::DISCOVERY_PORT);
+    let sweep = async move {
+        let mut arp = HashMap::new();
+        let mut announced = HashMap::new();
+        if interfaces.is_empty() && !targets.is_empty() && send_discovery {
+            announced = broadcast_discovery(None, targets, source, limiter).await;
+        }
+        for interface in interfaces {
+            let (replies, heard) = tokio::join!(
+                arp_sweep(interface.clone(), targets, limiter, cancel),
+                async {
+                    if send_discovery {
+                        broadcast_discovery(Some(interface), targets, source, limiter).await
+                    } else {
+                        HashMap::new()
+                    }
+                },
+            );
+            arp.extend(replies);
+            announced.extend(heard);
+        }
+        (arp, announced)
+    };
+    tokio::pin!(sweep);
+    let mut swept = false;
+    let mut arp = HashMap::new();
+    let mut announced = HashMap::new();
+    // Addresses that didn't answer before the sweep finished; any the sweep
+    // heard from are checked again once it has
+    let mut unanswered = Vec::new();
+
+    let total = targets.len() as usize;
+    let mut addresses = targets.iter();
+    let mut probed = 0;
+    loop {
+        // Checks pace their own packets, so start one whenever there's room
+        if probes.len() < context.max_in_flight {
+            if let Some(addr) = addresses.next() {
+                spawn_check(&mut probes, addr, false, swept, &arp, &announced, &context);
+                continue;
+            }
+        }
+        if probes.is_empty() && swept {
+            break;
+        }
+
+        tokio::select! {
+            (replies, heard) = &mut sweep, if !swept => {
+                swept = true;
+                arp = replies;
+                announced = heard;
+                for addr in unanswered.drain(..) {
+                    if arp.contains_key(&addr) || announced.contains_key(&addr) {
+                        spawn_check(&mut probes, addr, true, true, &arp, &announced, &context);
+                    }
+                }
+            }
+            Some(result) = probes.join_next(), if !probes.is_empty() => {
+                let Ok(result) = result else {
+                    continue;
+                };
+                if result.device.is_none() && !result.swept {
+                    if !swept {
+                        unanswered.push(result.addr);
+                    } else if arp.contains_key(&result.addr) || announced.contains_key(&result.addr) {
+                        spawn_check(&mut probes, result.addr, true, true, &arp, &announced, &context);
+                    }
+                }
+                if let Some(device) = result.device {
+                    if let Some(tx) = &events {
+                        tx.send(ScanEvent::Found(Box::new(device.clone()))).ok();
+                    }
+                    devices.push(device);
+                }
+                // Rechecked addresses were already counted
+                if !result.recheck {
+                    probed += 1;
+                    if let Some(tx) = &events {
+                        tx.send(ScanEvent::Progress { probed, total }).ok();
+                    }
+                }
+            }
+            _ = cancel.cancelled() => {
+                probes.abort_all();
+                break;
+            }
+        }
     }
 
-    for handle in handles {
-        if let Ok(Some(device)) = handle.await {
+    if options.ipv6 && !cancel.is_cancelled() {
+        let known_macs: HashSet<String> = devices.iter().map(|d| d.mac.clone()).collect();
+        for device in ipv6_devices(&ipv6_links, &known_macs, &context).await {
+            if let Some(tx) = &events {
+                tx.send(ScanEvent::Found(Box::new(device.clone()))).ok();
+            }
             devices.push(device);
         }
     }
 
+    devices.sort_by_key(Device::sort_key);
     Ok(devices)
 }
 
+// Interfaces to look for IPv6 hosts on: the source interface if one is set,
+// else those the IPv4 targets are on, else all of them
+fn ipv6_links(
+    local_networks: &[network_interface::NetworkInterface],
+    target_interfaces: &[network_interface::NetworkInterface],
+    source: Option<Ipv4Addr>,
+) -> Vec<String> {
+    let mut links: Vec<String> = match source {
+        Some(source) => local_networks
+            .iter()
+            .filter(|net| net.ip == source.to_string())
+            .map(|net| net.name.clone())
+            .collect(),
+        None => target_interfaces.iter().map(|net| net.name.clone()).collect(),
+    };
+    if links.is_empty() && source.is_none() {
+        links = local_networks
+            .iter()
+            .map(|net| net.name.clone())
+            .chain(network_interface::get_local_ipv6_networks().into_iter().map(|net| net.name))
+            .collect();
+    }
+    links.sort();
+    links.dedup();
+    links
+}
+
+// How long to collect replies to the all-nodes ping
+const ALL_NODES_WAIT: Duration = Duration::from_secs(1);
+
+// IPv6 hosts on `links`: whoever answers a ping to ff02::1, plus the IPv6
+// neighbor table. One device per MAC, at a global address when it has one,
+// skipping MACs already in `known_macs`.
+async fn ipv6_devices(links: &[String], known_macs: &HashSet<String>, context: &Arc<ProbeContext>) -> Vec<Device> {
+    let mut replies: HashMap<Ipv6Addr, (String, Duration)> = HashMap::new();
+    for link in links {
+        let Some(index) = network_interface::interface_index(link) else {
+            continue;
+        };
+        context.limiter.wait().await;
+        let answered = tokio::task::spawn_blocking(move || crate::icmp::ping_all_nodes(index, ALL_NODES_WAIT))
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .unwrap_or_default();
+        for (ip, rtt) in answered {
+            replies.insert(ip, (link.clone(), rtt));
+        }
+    }
+
+    // Hosts that answered have just resolved us, so they're in the table
+    // now. Windows lists interfaces by index, which can't be matched to a
+    // link name, so it keeps every entry.
+    let neighbors: Vec<_> = neighbor_table::load_ipv6()
+        .await
+        .into_iter()
+        .filter(|n| links.contains(&n.interface) || n.interface.parse::<u32>().is_ok())
+        .collect();
+
+    // (address, link, rtt) by MAC, preferring a global address
+    let mut by_mac: HashMap<String, (Ipv6Addr, String, Option<Duration>)> = HashMap::new();
+    let mut no_mac = Vec::new();
+    for neighbor in &neighbors {
+        let rtt = replies.get(&neighbor.ip).map(|(_, rtt)| *rtt);
+        let entry = by_mac
+            .entry(neighbor.mac.clone())
+            .or_insert_with(|| (neighbor.ip, neighbor.interface.clone(), rtt));
+        if is_link_local(&entry.0) && !is_link_local(&neighbor.ip) {
+            entry.0 = neighbor.ip;
+            entry.1 = neighbor.interface.clone();
+        }
+        entry.2 = entry.2.or(rtt);
+    }
+    for (ip, (link, rtt)) in &replies {
+        if !neighbors.iter().any(|n| n.ip == *ip) {
+            no_mac.push((*ip, link.clone(), Some(*rtt)));
+        }
+    }
+
+    let candidates = by_mac
+        .into_iter()
+        .filter(|(mac, _)| !known_macs.contains(mac))
+        .map(|(mac, (ip, link, rtt))| (Some(mac), ip, link, rtt))
+        .chain(no_mac.into_iter().map(|(ip, link, rtt)| (None, ip, link, rtt)));
+
+    let mut checks = tokio::task::JoinSet::new();
+    let mut devices = Vec::new();
+    for (mac, ip, link, rtt) in candidates {
+        if checks.len() >= context.max_in_flight {
+            if let Some(result) = checks.join_next().await {
+                devices.extend(result.ok());
+            }
+        }
+        let host = if is_link_local(&ip) {
+            format!("{}%{}", ip, link)
+        } else {
+            ip.to_string()
+        };
+        let context = Arc::clone(context);
+        checks.spawn(async move {
+            let ports = match network_interface::socket_addr(&host, 0) {
+                Ok(addr) => fingerprint::probe_ports(addr, &context.ports, None, &context.limiter).await,
+                Err(_) => Vec::new(),
+            };
+            let mac = mac.unwrap_or_else(|| String::from("Unknown"));
+            let hostname = find_hostname(&host, &mac, None, &context).await;
+            Device {
+                ip: host,
+                company: crate::oui_database::get_manufacturer(&mac),
+                mac,
+                selected: false,
+                status: DeviceStatus::Pending,
+                logs: String::new(),
+                has_ssh: ports.iter().any(OpenPort::is_ssh),
+                ports,
+                hostname,
+                rtt,
+                discovery: None,
+                info: None,
+                changed_host_key: None,
+                inform_received: false,
+            }
+        });
+    }
+
+    while let Some(result) = checks.join_next().await {
+        devices.extend(result.ok());
+    }
+    devices
+}
+
+fn is_link_local(ip: &Ipv6Addr) -> bool {
+    ip.segments()[0] & 0xffc0 == 0xfe80
+}
+
+/// Listen for the discovery announcements unadopted UniFi devices
+/// broadcast, without probing the network, and send each device that is
+/// heard as [`ScanEvent::Found`] until `cancel` is cancelled.
+///
+/// A device is sent the first time it is heard and again whenever what it
+/// announces changes (after adoption, say); repeats are dropped. Each one is
+/// pinged and has its ports probed like a scanned host, under the same
+/// `options` (the targets aside). Devices heard while `max_in_flight` are
+/// being checked wait their turn. Fails if UDP 10001 can't
+/// be bound or the DHCP lease file can't be read.
+pub async fn listen_for_announcements(
+    options: &ScanOptions,
+    cancel: &CancelToken,
+    events: tokio::sync::mpsc::UnboundedSender<ScanEvent>,
+) -> Result<(), String> {
+    let listener = discovery::Listener::bind()
+        .map_err(|e| format!("Can't listen on UDP {}: {}", discovery::DISCOVERY_PORT, e))?;
+    let leases = match &options.dhcp_lease_file {
+        Some(path) => Leases::load(path).await?,
+        None => Leases::default(),
+    };
+    let context = Arc::new(ProbeContext::new(options, leases).await);
+    let mut heard: HashMap<Ipv4Addr, DiscoveryInfo> = HashMap::new();
+    let mut checks = tokio::task::JoinSet::new();
+    let mut waiting: std::collections::VecDeque<(Ipv4Addr, DiscoveryInfo)> = std::collections::VecDeque::new();
+
+    loop {
+        while checks.len() < context.max_in_flight {
+            let Some((ip, info)) = waiting.pop_front() else {
+                break;
+            };
+            let context = Arc::clone(&context);
+            checks.spawn(async move {
+                check_device(ip.to_string(), None, Some(info), &context).await
+            });
+        }
+
+        tokio::select! {
+            received = listener.next() => {
+                let (ip, mut info) = received.map_err(|e| format!("Discovery listener failed: {}", e))?;
+                // Uptime changes with every announcement
+                info.uptime = None;
+                if heard.get(&ip) == Some(&info) {
+                    continue;
+                }
+                heard.insert(ip, info.clone());
+                waiting.retain(|(queued, _)| *queued != ip);
+                waiting.push_back((ip, info));
+            }
+            Some(result) = checks.join_next(), if !checks.is_empty() => {
+                if let Ok(Some(device)) = result {
+                    events.send(ScanEvent::Found(Box::new(device))).ok();
+                }
+            }
+            _ = cancel.cancelled() => {
+                checks.abort_all();
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Least delay between ARP requests; a probe rate can space them further
+const ARP_INTERVAL: Duration = Duration::from_micros(500);
+
+// ARP-sweep the targets on one directly attached subnet. Returns an empty
+// map when raw sockets aren't available.
+async fn arp_sweep(
+    interface: network_interface::NetworkInterface,
+    targets: &TargetSet,
+    limiter: &RateLimiter,
+    cancel: &CancelToken,
+) -> HashMap<Ipv4Addr, String> {
+    let Ok(source_ip) = interface.ip.parse::<Ipv4Addr>() else {
+        return HashMap::new();
+    };
+
+    let targets: Vec<Ipv4Addr> = targets
+        .iter()
+        .filter(|ip| interface.contains(*ip) && *ip != source_ip)
+        .collect();
+    let limiter = limiter.clone();
+    let cancel = cancel.clone();
+
+    tokio::task::spawn_blocking(move || {
+        arp::sweep(&interface.name, source_ip, &targets, ARP_INTERVAL, &limiter, &cancel)
+    })
+    .await
+    .ok()
+    .and_then(Result::ok)
+    .unwrap_or_default()
+}
+
+// How long to wait for discovery replies
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Broadcast a UniFi discovery probe on a local subnet (or to everyone when
+// none of the targets are local) and keep the replies from the targets
+async fn broadcast_discovery(
+    interface: Option<network_interface::NetworkInterface>,
+    targets: &TargetSet,
+    source: Option<Ipv4Addr>,
+    limiter: &RateLimiter,
+) -> HashMap<Ipv4Addr, DiscoveryInfo> {
+    let target = interface
+        .and_then(|net| net.broadcast())
+        .unwrap_or(Ipv4Addr::BROADCAST);
+    limiter.wait().await;
+    let mut replies = discovery::broadcast(target, source, DISCOVERY_TIMEOUT).await;
+    replies.retain(|ip, _| targets.contains(*ip));
+    replies
+}
+
+// One finished check of an address
+struct ProbeResult {
+    addr: Ipv4Addr,
+    // A second check after the sweep heard from an address that didn't answer
+    recheck: bool,
+    // Whether the ARP and discovery sweep results were known to the check
+    swept: bool,
+    device: Option<Device>,
+}
+
+// Start checking `addr` with whatever the sweep has found so far
+fn spawn_check(
+    probes: &mut tokio::task::JoinSet<ProbeResult>,
+    addr: Ipv4Addr,
+    recheck: bool,
+    swept: bool,
+    arp: &HashMap<Ipv4Addr, String>,
+    announced: &HashMap<Ipv4Addr, DiscoveryInfo>,
+    context: &Arc<ProbeContext>,
+) {
+    let arp_mac = arp.get(&addr).cloned();
+    let heard = announced.get(&addr).cloned();
+    let context = Arc::clone(context);
+    probes.spawn(async move {
+        let device = check_device(addr.to_string(), arp_mac, heard, &context).await;
+        ProbeResult { addr, recheck, swept, device }
+    });
+}
+
 fn parse_ip(ip_str: &str) -> Result<u32, String> {
     let parts: Vec<&str> = ip_str.split('.').collect();
     if parts.len() != 4 {
@@ -51,24 +670,102 @@ fn parse_ip(ip_str: &str) -> Result<u32, String> {
     Ok(ip_num)
 }
 
-async fn check_device(ip: String) -> Option<Device> {
-    // First, try to ping the device to see if it's alive
-    let is_alive = ping_device(&ip).await;
+// The OS neighbor table, read once when a scan starts. Pinging a host adds
+// its entry, so a live host that isn't in it yet triggers a reload, at most
+// once per NEIGHBOR_RELOAD_INTERVAL across the whole scan.
+struct NeighborCache {
+    table: HashMap<Ipv4Addr, String>,
+    loaded: std::time::Instant,
+}
+
+const NEIGHBOR_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+impl NeighborCache {
+    async fn load() -> Self {
+        Self {
+            table: neighbor_table::load().await,
+            loaded: std::time::Instant::now(),
+        }
+    }
+
+    async fn lookup(cache: &Mutex<Self>, ip: Ipv4Addr) -> Option<String> {
+        let mut cache = cache.lock().await;
+        if !cache.table.contains_key(&ip) && cache.loaded.elapsed() >= NEIGHBOR_RELOAD_INTERVAL {
+            *cache = Self::load().await;
+        }
+        cache.table.get(&ip).cloned()
+    }
+}
+
+// What every probe in a scan (or announcement listener) shares
+struct ProbeContext {
+    source: Option<Ipv4Addr>,
+    ports: Vec<u16>,
+    max_in_flight: usize,
+    limiter: RateLimiter,
+    neighbors: Mutex<NeighborCache>,
+    leases: Leases,
+}
+
+impl ProbeContext {
+    async fn new(options: &ScanOptions, leases: Leases) -> Self {
+        Self {
+            source: options.source_ip,
+            ports: options.ports.clone(),
+            max_in_flight: options.max_in_flight.max(1),
+            limiter: RateLimiter::new(options.probes_per_second),
+            neighbors: Mutex::new(NeighborCache::load().await),
+            leases,
+        }
+    }
+}
 
-    if !is_alive {
+// `arp_mac` is set when the host answered the ARP sweep, `announced` when
+// it answered the broadcast discovery probe.
+async fn check_device(
+    ip: String,
+    arp_mac: Option<String>,
+    announced: Option<DiscoveryInfo>,
+    context: &ProbeContext,
+) -> Option<Device> {
+    let addr: Ipv4Addr = ip.parse().ok()?;
+    let source = context.source;
+
+    // Ping the device and ask it directly whether it's a UniFi device
+    let ping = async {
+        context.limiter.wait().await;
+        ping_device(&ip, source).await
+    };
+    let (rtt, discovery) = tokio::join!(ping, async {
+        match announced {
+            Some(info) => Some(info),
+            None if context.ports.contains(&discovery::DISCOVERY_PORT) => {
+                context.limiter.wait().await;
+                discovery::probe(addr, source, DISCOVERY_TIMEOUT).await
+            }
+            None => None,
+        }
+    });
+    if rtt.is_none() && arp_mac.is_none() && discovery.is_none() {
         return None;
     }
 
-    // Device is alive, check if it has SSH
-    let has_ssh = check_ssh(&ip).await;
+    // Device is alive, see what it's running
+    let ports = fingerprint::probe_ports(SocketAddr::from((addr, 0)), &context.ports, source, &context.limiter).await;
 
     // On Windows, give a tiny delay for ARP cache to populate after ping
     #[cfg(target_os = "windows")]
     tokio::time::sleep(Duration::from_millis(50)).await;
 
-    // Try to get MAC address via ARP or use placeholder
-    let mac = get_mac_address(&ip).await.unwrap_or_else(|| String::from("Unknown"));
+    // Look the MAC up in the neighbor table or use placeholder
+    let discovered_mac = discovery.as_ref().map(|d| d.mac.clone()).filter(|m| !m.is_empty());
+    let mac = match arp_mac.or(discovered_mac) {
+        Some(mac) => Some(mac),
+        None => NeighborCache::lookup(&context.neighbors, addr).await,
+    }
+    .unwrap_or_else(|| String::from("Unknown"));
     let company = crate::oui_database::get_manufacturer(&mac);
+    let hostname = find_hostname(&ip, &mac, discovery.as_ref(), context).await;
 
     Some(Device {
         ip,
@@ -77,11 +774,56 @@ async fn check_device(ip: String) -> Option<Device> {
         selected: false,
         status: DeviceStatus::Pending,
         logs: String::new(),
-        has_ssh,
+        has_ssh: ports.iter().any(OpenPort::is_ssh),
+        ports,
+        hostname,
+        rtt,
+        discovery,
+        info: None,
+        changed_host_key: None,
+        inform_received: false,
     })
 }
 
-async fn ping_device(ip: &str) -> bool {
+// How long each of the reverse DNS and mDNS lookups may take
+const HOSTNAME_TIMEOUT: Duration = Duration::from_secs(1);
+
+// The lease file's name for the device, else the one it gave in its
+// discovery reply, else whatever reverse DNS or mDNS says
+async fn find_hostname(
+    host: &str,
+    mac: &str,
+    discovery: Option<&DiscoveryInfo>,
+    context: &ProbeContext,
+) -> Option<String> {
+    let known = context
+        .leases
+        .hostname(mac, host)
+        .or(discovery.map(|d| d.hostname.as_str()))
+        .filter(|name| !name.is_empty());
+    match known {
+        Some(name) => Some(name.to_string()),
+        None => hostname::resolve(host, HOSTNAME_TIMEOUT, &context.limiter).await,
+    }
+}
+
+// Timeout for a single echo request
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Round-trip time if the host answered a ping
+async fn ping_device(ip: &str, source: Option<Ipv4Addr>) -> Option<Duration> {
+    if let Ok(addr) = ip.parse() {
+        let native = tokio::task::spawn_blocking(move || crate::icmp::ping(addr, source, PING_TIMEOUT)).await;
+        if let Ok(Ok(rtt)) = native {
+            return rtt;
+        }
+    }
+
+    // No ICMP socket available (e.g. Windows without admin rights)
+    ping_with_command(ip, source).await
+}
+
+async fn ping_with_command(ip: &str, source: Option<Ipv4Addr>) -> Option<Duration> {
     #[cfg(target_os = "macos")]
     let ping_cmd = "ping";
 
@@ -98,7 +840,17 @@ async fn ping_device(ip: &str) -> bool {
     let args = vec!["-n", "1", "-w", "1000", ip];
 
     let mut cmd = tokio::process::Command::new(ping_cmd);
+    if let Some(source) = source {
+        // Linux ping takes the source as -I; macOS and Windows as -S
+        #[cfg(target_os = "linux")]
+        cmd.arg("-I");
+        #[cfg(not(target_os = "linux"))]
+        cmd.arg("-S");
+        cmd.arg(source.to_string());
+    }
     cmd.args(&args);
+    // Don't leave ping running when a cancelled scan aborts this probe
+    cmd.kill_on_drop(true);
 
     // Hide console window on Windows
     #[cfg(target_os = "windows")]
@@ -108,108 +860,66 @@ async fn ping_device(ip: &str) -> bool {
         cmd.creation_flags(CREATE_NO_WINDOW);
     }
 
-    match cmd.output().await {
-        Ok(output) => output.status.success(),
-        Err(_) => false,
+    let start = std::time::Instant::now();
+    let output = cmd.output().await.ok()?;
+    if !output.status.success() {
+        return None;
     }
-}
 
-async fn check_ssh(ip: &str) -> bool {
-    let addr = format!("{}:22", ip);
+    // Process start-up inflates the measured time, so prefer ping's own
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(parse_ping_time(&stdout).unwrap_or_else(|| start.elapsed()))
+}
 
-    match tokio::time::timeout(
-        Duration::from_millis(500),
-        tokio::net::TcpStream::connect(&addr)
-    ).await {
-        Ok(Ok(_)) => true,
-        _ => false,
-    }
+// Pull the round trip out of a `time=0.42 ms` or `time<1ms` reply line
+fn parse_ping_time(output: &str) -> Option<Duration> {
+    let pos = output.find("time=").or_else(|| output.find("time<"))?;
+    let value: String = output[pos + 5..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let ms: f64 = value.parse().ok()?;
+    Some(Duration::from_secs_f64(ms / 1000.0))
 }
 
-async fn get_mac_address(ip: &str) -> Option<String> {
-    // Try to get MAC address from ARP cache
-    #[cfg(target_os = "macos")]
-    {
-        if let Ok(output) = tokio::process::Command::new("arp")
-            .arg("-n")
-            .arg(ip)
-            .output()
-            .await
-        {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            // Parse ARP output to extract MAC address
-            for line in stdout.lines() {
-                if line.contains(ip) {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 4 {
-                        let mac = parts[3].to_string();
-                        if mac.contains(':') {
-                            return Some(mac.to_uppercase());
-                        }
-                    }
-                }
-            }
-        }
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    #[cfg(target_os = "linux")]
-    {
-        if let Ok(output) = tokio::process::Command::new("ip")
-            .arg("neigh")
-            .arg("show")
-            .output()
-            .await
-        {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                if line.contains(ip) {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    for (i, part) in parts.iter().enumerate() {
-                        if *part == "lladdr" && i + 1 < parts.len() {
-                            return Some(parts[i + 1].to_uppercase());
-                        }
-                    }
-                }
-            }
-        }
+    fn ip(s: &str) -> Ipv4Addr {
+        s.parse().unwrap()
     }
 
-    #[cfg(target_os = "windows")]
-    {
-        // Try with full ARP table first (more reliable on Windows)
-        let mut cmd = tokio::process::Command::new("arp");
-        cmd.arg("-a");
+    #[test]
+    fn cidr_skips_network_and_broadcast() {
+        let targets = TargetSet::parse("10.0.8.0/22").unwrap();
+        assert_eq!(targets.len(), 1022);
+        assert!(!targets.contains(ip("10.0.8.0")));
+        assert!(targets.contains(ip("10.0.8.1")));
+        assert!(targets.contains(ip("10.0.11.254")));
+        assert!(!targets.contains(ip("10.0.11.255")));
 
-        // Hide console window on Windows
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
-        use std::os::windows::process::CommandExt;
-        cmd.creation_flags(CREATE_NO_WINDOW);
+        assert_eq!(TargetSet::parse("10.0.0.5/32").unwrap().len(), 1);
+        assert_eq!(TargetSet::parse("10.0.0.4/31").unwrap().len(), 2);
+    }
 
-        if let Ok(output) = cmd.output().await {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            // Windows ARP output format:
-            // Internet Address      Physical Address      Type
-            // 192.168.1.1           aa-bb-cc-dd-ee-ff     dynamic
-            for line in stdout.lines() {
-                // Look for line containing our IP
-                if line.contains(ip) {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    // parts[0] = IP, parts[1] = MAC, parts[2] = type
-                    if parts.len() >= 2 {
-                        let mac_str = parts[1];
-                        // Check if it's a valid MAC (not "incomplete" or other status)
-                        if mac_str.contains('-') || mac_str.contains(':') {
-                            let mac = mac_str.replace("-", ":");
-                            // Filter out incomplete or invalid MACs
-                            if mac.len() >= 17 {  // AA:BB:CC:DD:EE:FF is 17 chars
-                                return Some(mac.to_uppercase());
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    #[test]
+    fn ranges_hosts_and_exclusions() {
+        let targets = TargetSet::parse("192.168.1.10-20, 192.168.1.15-192.168.1.30, 10.0.0.1, !192.168.1.12, !192.168.1.25-192.168.1.40").unwrap();
+        assert_eq!(targets.to_string(), "10.0.0.1, 192.168.1.10-192.168.1.11, 192.168.1.13-192.168.1.24");
+        assert_eq!(targets.len(), 15);
+        assert_eq!(targets.iter().next(), Some(ip("10.0.0.1")));
+        assert_eq!(targets.iter().nth(1), Some(ip("192.168.1.10")));
     }
 
-    None
+    #[test]
+    fn rejects_bad_input() {
+        assert!(TargetSet::parse("").is_err());
+        assert!(TargetSet::parse("!10.0.0.1").is_err());
+        assert!(TargetSet::parse("10.0.0.1, !10.0.0.1").is_err());
+        assert!(TargetSet::parse("10.0.0.256").is_err());
+        assert!(TargetSet::parse("10.0.0.0/33").is_err());
+        assert!(TargetSet::parse("10.0.0.20-10").is_err());
+        assert!(TargetSet::parse("10.0.0.0/8").is_err());
+    }
 }
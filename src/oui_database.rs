@@ -6,8 +6,9 @@ static OUI_MAP: OnceLock<HashMap<String, String>> = OnceLock::new();
 // Embed the OUI database file directly into the binary
 const OUI_DATABASE_EMBEDDED: &str = include_str!("../oui-database.txt");
 
+/// Look up the vendor for a MAC address (any `AA:BB:CC:...` form).
 pub fn get_manufacturer(mac: &str) -> String {
-    let oui_map = OUI_MAP.get_or_init(|| load_oui_database());
+    let oui_map = OUI_MAP.get_or_init(load_oui_database);
 
     if mac == "Unknown" {
         return String::from("Unknown");
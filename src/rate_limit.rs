@@ -0,0 +1,91 @@
+use crate::cancel::CancelToken;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Spaces out the packets a scan sends so that, all together, they stay
+/// under a set rate.
+///
+/// Clones share one schedule, so every part of a scan (pings, discovery and
+/// port probes, name lookups, the ARP sweep) draws from the same budget.
+/// Each packet reserves the next free slot before it goes out. The default
+/// limiter doesn't wait at all.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    schedule: Option<Arc<Schedule>>,
+}
+
+#[derive(Debug)]
+struct Schedule {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    /// A limiter for `per_second` packets a second, or one that never waits
+    /// for `None` (or zero).
+    pub fn new(per_second: Option<u32>) -> Self {
+        let schedule = per_second.filter(|&rate| rate > 0).map(|rate| {
+            Arc::new(Schedule {
+                interval: Duration::from_secs_f64(1.0 / rate as f64),
+                next: Mutex::new(Instant::now()),
+            })
+        });
+        Self { schedule }
+    }
+
+    /// Wait for this packet's slot.
+    pub async fn wait(&self) {
+        if let Some(slot) = self.reserve() {
+            tokio::time::sleep_until(slot.into()).await;
+        }
+    }
+
+    /// Block the current thread until this packet's slot, waking early on
+    /// cancel. Returns true if `cancel` was cancelled.
+    pub fn wait_blocking(&self, cancel: &CancelToken) -> bool {
+        match self.reserve() {
+            Some(slot) => cancel.sleep(slot.saturating_duration_since(Instant::now())),
+            None => cancel.is_cancelled(),
+        }
+    }
+
+    // Take the next free slot; `None` when there's no limit
+    fn reserve(&self) -> Option<Instant> {
+        let schedule = self.schedule.as_ref()?;
+        let mut next = schedule.next.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let slot = (*next).max(Instant::now());
+        *next = slot + schedule.interval;
+        Some(slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlimited_never_waits() {
+        assert_eq!(RateLimiter::default().reserve(), None);
+        assert_eq!(RateLimiter::new(None).reserve(), None);
+        assert_eq!(RateLimiter::new(Some(0)).reserve(), None);
+    }
+
+    #[test]
+    fn slots_are_spaced_by_the_rate() {
+        let limiter = RateLimiter::new(Some(10));
+        let first = limiter.reserve().unwrap();
+        let second = limiter.reserve().unwrap();
+        let third = limiter.reserve().unwrap();
+        assert_eq!(second - first, Duration::from_millis(100));
+        assert_eq!(third - second, Duration::from_millis(100));
+    }
+
+    #[test]
+    fn clones_share_the_schedule() {
+        let limiter = RateLimiter::new(Some(100));
+        let other = limiter.clone();
+        let first = limiter.reserve().unwrap();
+        let second = other.reserve().unwrap();
+        assert_eq!(second - first, Duration::from_millis(10));
+    }
+}
@@ -1,6 +1,11 @@
-use ssh2::Session;
+use crate::cancel::CancelToken;
+use crate::config::{AppConfig, Credentials, HostKeyPolicy};
+use crate::device_info::{parse_info, DeviceInfo};
+use crate::known_hosts::{self, HostKey, Verdict};
+use ssh2::{Channel, HashType, Session};
 use std::io::{Read, Write};
 use std::net::TcpStream;
+use std::path::Path;
 use std::time::Duration;
 
 fn strip_ansi_codes(s: &str) -> String {
@@ -45,127 +50,667 @@ fn send_output(
     }
 }
 
+/// Settings for [`execute_adoption`] that are shared by every device in a run.
+#[derive(Debug, Clone, Default)]
+pub struct AdoptionOptions {
+    pub controller_url: String,
+    /// Tried in order until one authenticates.
+    pub credentials: Vec<Credentials>,
+    pub host_key_policy: HostKeyPolicy,
+    /// SSH port when neither the device address nor the credential set
+    /// gives one.
+    pub ssh_port: u16,
+    /// How long to keep polling `info` after `set-inform` for the device to
+    /// report it reached the controller. `None` skips verification.
+    pub verify_timeout: Option<Duration>,
+    /// While verifying, re-send `set-inform` this often until the device
+    /// reports `Connected`. Needs `verify_timeout`.
+    pub reinform_interval: Option<Duration>,
+}
+
+impl AdoptionOptions {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            controller_url: config.controller_url.clone(),
+            credentials: config.credentials.clone(),
+            host_key_policy: config.host_key_policy,
+            ssh_port: config.ssh_port,
+            // Re-informing waits on the verification loop, so it turns it on
+            verify_timeout: (config.verify_adoption || config.reinform_until_connected)
+                .then(|| Duration::from_secs(config.verify_timeout_secs)),
+            reinform_interval: config
+                .reinform_until_connected
+                .then(|| Duration::from_secs(config.reinform_interval_secs)),
+        }
+    }
+}
+
+// Delay between `info` polls while verifying
+const VERIFY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// How long the shell gets to print its first prompt after login
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long a command may run before we stop waiting for the prompt
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(20);
+
+// A line that looks like a prompt but isn't the one the shell started with
+// (or before that one is known) only counts once the device has been quiet
+// this long, so output that happens to end in `#` doesn't cut a command short
+const PROMPT_QUIET_PERIOD: Duration = Duration::from_millis(300);
+
+// Delay between reads while the device has nothing to say
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// An interactive shell on a device. The session must outlive the channel.
+struct Shell {
+    _session: Session,
+    channel: Channel,
+    // The prompt the shell printed after login, e.g. `UBNT-BZ.v4.3.20#`
+    prompt: Option<String>,
+}
+
+// The shell prompt `output` ends with, if any: an unterminated last line
+// ending in `#` or `$`, such as `UBNT-BZ.v4.3.20# ` or `admin@US-8:~$ `.
+// `output` is expected without ANSI codes or carriage returns.
+fn trailing_prompt(output: &str) -> Option<&str> {
+    let last = output.rsplit('\n').next()?.trim();
+    (last.ends_with('#') || last.ends_with('$')).then_some(last)
+}
+
+// What came out of the shell while waiting for a prompt
+struct ShellOutput {
+    text: String,
+    // Whether the prompt came back; false on timeout, EOF or cancel
+    prompt_seen: bool,
+}
+
+// Read from the shell until it prints `expected` (any prompt, after a quiet
+// spell, when that's `None` or doesn't show), the channel closes, `cancel`
+// fires or `timeout` passes. With `log` set each chunk is also appended to
+// the session log as it arrives.
+fn read_until_prompt(
+    channel: &mut Channel,
+    expected: Option<&str>,
+    timeout: Duration,
+    log: bool,
+    output: &mut String,
+    progress_tx: &Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    cancel: &CancelToken,
+) -> ShellOutput {
+    let mut text = String::new();
+    let mut buf = vec![0u8; 4096];
+    let start = std::time::Instant::now();
+    let mut last_data = std::time::Instant::now();
+    loop {
+        if let Ok(n @ 1..) = channel.read(&mut buf) {
+            let chunk = String::from_utf8_lossy(&buf[..n]);
+            text.push_str(&strip_ansi_codes(&chunk));
+            if log {
+                send_output(output, &chunk, progress_tx);
+            }
+            last_data = std::time::Instant::now();
+            continue;
+        }
+
+        if let Some(prompt) = trailing_prompt(&text) {
+            if expected == Some(prompt) || last_data.elapsed() >= PROMPT_QUIET_PERIOD {
+                return ShellOutput { text, prompt_seen: true };
+            }
+        }
+        if channel.eof() || cancel.is_cancelled() || start.elapsed() >= timeout {
+            return ShellOutput { text, prompt_seen: false };
+        }
+        std::thread::sleep(READ_POLL_INTERVAL);
+    }
+}
+
+/// Why [`execute_adoption`] stopped. Every variant carries the session log,
+/// whose last line is the reason.
+#[derive(Debug, Clone)]
+pub enum AdoptionError {
+    Failed(String),
+    /// The device presented a different host key than the one on record.
+    /// `presented` can be passed to [`known_hosts::trust`] with `address`
+    /// (the device's address, with the port unless it is 22) to accept it.
+    HostKeyMismatch { log: String, address: String, presented: HostKey },
+    /// The caller cancelled the session; the shell was closed first.
+    Cancelled(String),
+}
+
+impl AdoptionError {
+    pub fn log(&self) -> &str {
+        match self {
+            AdoptionError::Failed(log) => log,
+            AdoptionError::HostKeyMismatch { log, .. } => log,
+            AdoptionError::Cancelled(log) => log,
+        }
+    }
+}
+
+/// What a successful session produced.
+#[derive(Debug, Clone)]
+pub struct AdoptionReport {
+    /// Full session log.
+    pub log: String,
+    /// Parsed `info` output, when the device answered it. After adoption
+    /// this is the last status seen during verification.
+    pub info: Option<DeviceInfo>,
+    /// Whether the device confirmed it reached the controller. `None` when
+    /// verification was off.
+    pub confirmed: Option<bool>,
+}
+
+/// SSH into `ip` and point the device at the controller with `set-inform`.
+///
+/// `ip` may name the SSH port (`192.168.1.20:2222`, `[fd00::20]:2222`);
+/// otherwise the credential set's port is used, or failing that the one in
+/// the options.
+///
+/// The device's host key is checked against known_hosts (keyed by `mac` when
+/// known, otherwise the address and any port other than 22) according to
+/// the host key policy before any credentials are sent.
+///
+/// Each credential set is tried in order on a fresh connection until one
+/// authenticates; the log records which set was accepted. Within a set,
+/// ssh-agent is tried first, then the private key, then the password.
+///
+/// Before `set-inform`, `info` is run to identify the device. Devices already
+/// connected to this controller are left alone. Afterwards, if
+/// `verify_timeout` is set, `info` is polled until the device reports
+/// `Connected` or `Adopting` for this controller; with `reinform_interval`
+/// `set-inform` is repeated until it reports `Connected`.
+///
+/// Session output is streamed to `progress_tx` as it arrives. Both the `Ok`
+/// and `Err` values carry the full session log.
+///
+/// Cancelling `cancel` stops the session at the next step: the shell is
+/// closed and [`AdoptionError::Cancelled`] is returned.
 pub fn execute_adoption(
     ip: &str,
-    username: &str,
-    password: &str,
-    controller_url: &str,
+    mac: Option<&str>,
+    options: &AdoptionOptions,
     progress_tx: Option<tokio::sync::mpsc::UnboundedSender<String>>,
-) -> Result<String, String> {
+    cancel: &CancelToken,
+) -> Result<AdoptionReport, AdoptionError> {
     let mut output = String::new();
+    let mut shell = open_shell(ip, mac, options, &mut output, &progress_tx, cancel)?;
 
-    // Helper to format errors with the output so far
-    let make_error = |output: &str, error: &str| -> String {
-        format!("{}\n{}", output, error)
+    // Pre-flight: find out what we're talking to
+    let info = query_info(&mut shell, &mut output, &progress_tx, cancel);
+    check_cancelled(&mut shell, &mut output, &progress_tx, cancel)?;
+    let inform_url = format!("{}/inform", options.controller_url);
+
+    if info.as_ref().is_some_and(|info| info.is_connected_to(&inform_url)) {
+        let msg = format!("\nAlready connected to {}, skipping set-inform\n", inform_url);
+        send_output(&mut output, &msg, &progress_tx);
+        close_shell(&mut shell.channel);
+        return Ok(AdoptionReport { log: output, info, confirmed: Some(true) });
+    }
+
+    let command = format!("set-inform {}", inform_url);
+    if let Err(e) = run_shell_command(&mut shell, &command, &mut output, &progress_tx, cancel) {
+        return Err(AdoptionError::Failed(format!("{}\n{}", output, e)));
+    }
+    check_cancelled(&mut shell, &mut output, &progress_tx, cancel)?;
+
+    let (info, confirmed) = match options.verify_timeout {
+        Some(timeout) => {
+            let (latest, confirmed) = wait_for_controller(
+                &mut shell,
+                ip,
+                mac,
+                options,
+                &inform_url,
+                timeout,
+                &mut output,
+                &progress_tx,
+                cancel,
+            );
+            check_cancelled(&mut shell, &mut output, &progress_tx, cancel)?;
+            (latest.or(info), Some(confirmed))
+        }
+        None => (info, None),
     };
 
-    // Show the SSH connection attempt
-    let connection_msg = format!("{}@{}\n", username, ip);
-    send_output(&mut output, &connection_msg, &progress_tx);
-
-    // 1. Connect to device
-    let tcp = match TcpStream::connect_timeout(
-        &format!("{}:22", ip).parse().unwrap(),
-        Duration::from_secs(10)
-    ) {
-        Ok(tcp) => tcp,
-        Err(e) => return Err(make_error(&output, &format!("Connection failed: {}", e))),
+    close_shell(&mut shell.channel);
+
+    Ok(AdoptionReport { log: output, info, confirmed })
+}
+
+// Poll `info` until the device reports it reached `inform_url` or `timeout`
+// elapses. Returns the last info seen and whether it was confirmed.
+//
+// With `reinform_interval` set, `set-inform` is re-sent on that interval and
+// only `Connected` counts, which completes classic adoption once the device
+// is approved in the controller. If the device drops the session (it often
+// does once the controller provisions it) we log in again through the
+// credential chain and carry on.
+#[allow(clippy::too_many_arguments)]
+fn wait_for_controller(
+    shell: &mut Shell,
+    ip: &str,
+    mac: Option<&str>,
+    options: &AdoptionOptions,
+    inform_url: &str,
+    timeout: Duration,
+    output: &mut String,
+    progress_tx: &Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    cancel: &CancelToken,
+) -> (Option<DeviceInfo>, bool) {
+    let msg = match options.reinform_interval {
+        Some(interval) => format!(
+            "\nApprove the device in the controller; re-sending set-inform every {}s for up to {}s\n",
+            interval.as_secs(),
+            timeout.as_secs()
+        ),
+        None => format!("\nWaiting up to {}s for the device to reach the controller\n", timeout.as_secs()),
     };
+    send_output(output, &msg, progress_tx);
 
-    if let Err(e) = tcp.set_read_timeout(Some(Duration::from_secs(30))) {
-        return Err(make_error(&output, &format!("Failed to set timeout: {}", e)));
+    let start = std::time::Instant::now();
+    let mut last_inform = std::time::Instant::now();
+    let mut inform_count = 1;
+    let mut latest = None;
+    loop {
+        if cancel.sleep(VERIFY_POLL_INTERVAL) {
+            return (latest, false);
+        }
+
+        if shell.channel.eof() {
+            send_output(output, "\nSession closed by the device, reconnecting\n", progress_tx);
+            match open_shell(ip, mac, options, output, progress_tx, cancel) {
+                Ok(new_shell) => *shell = new_shell,
+                Err(e) => {
+                    let msg = format!("Reconnect failed: {}\n", e.log().lines().last().unwrap_or(""));
+                    send_output(output, &msg, progress_tx);
+                }
+            }
+        }
+
+        if let Some(info) = query_info(shell, output, progress_tx, cancel) {
+            let done = if options.reinform_interval.is_some() {
+                info.is_connected_to(inform_url)
+            } else {
+                info.has_reached(inform_url)
+            };
+            if done {
+                let msg = format!("\nConfirmed: device reports {}\n", info.inform_state());
+                send_output(output, &msg, progress_tx);
+                return (Some(info), true);
+            }
+            latest = Some(info);
+        }
+
+        if start.elapsed() >= timeout {
+            let state = latest.as_ref().map_or("no answer", |i| i.inform_state());
+            let msg = format!(
+                "\nUnconfirmed: inform sent {} time(s) but the device still reports {} after {}s\n",
+                inform_count,
+                state,
+                timeout.as_secs()
+            );
+            send_output(output, &msg, progress_tx);
+            return (latest, false);
+        }
+
+        if let Some(interval) = options.reinform_interval {
+            if last_inform.elapsed() >= interval {
+                inform_count += 1;
+                let state = latest.as_ref().map_or("no answer", |i| i.inform_state());
+                let msg = format!(
+                    "\nDevice reports {}, re-sending set-inform (attempt {})\n",
+                    state, inform_count
+                );
+                send_output(output, &msg, progress_tx);
+                let command = format!("set-inform {}", inform_url);
+                run_shell_command(shell, &command, output, progress_tx, cancel).ok();
+                last_inform = std::time::Instant::now();
+            }
+        }
     }
+}
 
-    let mut sess = Session::new().map_err(|e| format!("Failed to create session: {}", e))?;
-    sess.set_tcp_stream(tcp);
+/// Log in like [`execute_adoption`] but only run `info`, without changing
+/// anything on the device.
+pub fn identify_device(
+    ip: &str,
+    mac: Option<&str>,
+    options: &AdoptionOptions,
+    progress_tx: Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    cancel: &CancelToken,
+) -> Result<AdoptionReport, AdoptionError> {
+    let mut output = String::new();
+    let mut shell = open_shell(ip, mac, options, &mut output, &progress_tx, cancel)?;
 
-    if let Err(e) = sess.handshake() {
-        return Err(make_error(&output, &format!("SSH handshake failed: {}", e)));
+    let info = query_info(&mut shell, &mut output, &progress_tx, cancel);
+    check_cancelled(&mut shell, &mut output, &progress_tx, cancel)?;
+    close_shell(&mut shell.channel);
+
+    match info {
+        Some(info) => Ok(AdoptionReport { log: output, info: Some(info), confirmed: None }),
+        None => Err(AdoptionError::Failed(format!(
+            "{}\nDevice did not answer `info`; it may not be a UniFi device",
+            output
+        ))),
     }
+}
 
-    // 2. Authenticate
-    if let Err(e) = sess.userauth_password(username, password) {
-        return Err(make_error(&output, &format!("Authentication failed: {}", e)));
+// Connect, verify the host key, authenticate and start an interactive shell.
+// The session is returned alongside the channel to keep it alive.
+fn open_shell(
+    ip: &str,
+    mac: Option<&str>,
+    options: &AdoptionOptions,
+    output: &mut String,
+    progress_tx: &Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    cancel: &CancelToken,
+) -> Result<Shell, AdoptionError> {
+    let credentials = &options.credentials;
+
+    // Helper to format errors with the output so far
+    let make_error = |output: &str, error: &str| -> AdoptionError {
+        AdoptionError::Failed(format!("{}\n{}", output, error))
+    };
+
+    if credentials.is_empty() {
+        return Err(make_error(output, "No credential sets configured"));
     }
+    let (host, device_port) = match crate::network_interface::split_host_port(ip) {
+        Ok(parts) => parts,
+        Err(e) => return Err(make_error(output, &e)),
+    };
+
+    // 1-2. Connect and authenticate, falling through the credential chain
+    let mut authenticated = None;
+    // Sets can use different ports, so one that can't connect doesn't end the chain
+    let mut connected = false;
+    for creds in credentials {
+        if cancel.is_cancelled() {
+            send_output(output, "\nCancelled\n", progress_tx);
+            return Err(AdoptionError::Cancelled(output.clone()));
+        }
+
+        // Show the SSH connection attempt
+        let port = device_port.or(creds.port).unwrap_or(options.ssh_port);
+        let address = crate::network_interface::join_host_port(host, port);
+        let connection_msg = format!("{}@{} ({})\n", creds.username, address, creds.name);
+        send_output(output, &connection_msg, progress_tx);
 
-    if !sess.authenticated() {
-        return Err(make_error(&output, "Authentication failed: Invalid credentials"));
+        let sess = match connect(host, port) {
+            Ok(sess) => sess,
+            Err(e) => {
+                let msg = format!("Credential set '{}' can't connect: {}\n", creds.name, e);
+                send_output(output, &msg, progress_tx);
+                continue;
+            }
+        };
+        connected = true;
+
+        // Verify the host key before sending any credentials
+        let presented = match host_key(&sess) {
+            Some(key) => key,
+            None => return Err(make_error(output, "Device did not present a host key")),
+        };
+        match known_hosts::verify(&address, mac, &presented, options.host_key_policy) {
+            Verdict::Trusted => {}
+            Verdict::Learned => {
+                let msg = format!("New host key {} saved to known_hosts\n", presented);
+                send_output(output, &msg, progress_tx);
+            }
+            Verdict::Unknown => {
+                return Err(make_error(
+                    output,
+                    &format!(
+                        "Host key verification failed: {} is not in known_hosts and the policy is strict (presented {})",
+                        address, presented
+                    ),
+                ))
+            }
+            Verdict::Mismatch { stored } => {
+                let error = format!(
+                    "Host key verification failed: key for {} changed (stored {}, presented {}). \
+                     If the device was factory reset, trust the new key and adopt again.",
+                    address, stored, presented
+                );
+                return Err(AdoptionError::HostKeyMismatch {
+                    log: format!("{}\n{}", output, error),
+                    address,
+                    presented,
+                });
+            }
+        }
+
+        match authenticate(&sess, creds, output, progress_tx) {
+            Ok(()) => {
+                let msg = format!("Authenticated with credential set '{}'\n", creds.name);
+                send_output(output, &msg, progress_tx);
+                authenticated = Some(sess);
+                break;
+            }
+            Err(e) => {
+                let msg = format!("Credential set '{}' rejected: {}\n", creds.name, e);
+                send_output(output, &msg, progress_tx);
+            }
+        }
     }
 
+    let sess = match authenticated {
+        Some(sess) => sess,
+        None if !connected => {
+            return Err(make_error(
+                output,
+                "Connection failed: no credential set's port accepted a connection",
+            ))
+        }
+        None => {
+            return Err(make_error(
+                output,
+                "Authentication failed: no credential set was accepted",
+            ))
+        }
+    };
+
     // 3. Open an interactive shell
     let mut channel = match sess.channel_session() {
         Ok(ch) => ch,
-        Err(e) => return Err(make_error(&output, &format!("Failed to open channel: {}", e))),
+        Err(e) => return Err(make_error(output, &format!("Failed to open channel: {}", e))),
     };
 
     // Request a PTY for interactive shell
     if let Err(e) = channel.request_pty("xterm", None, None) {
-        return Err(make_error(&output, &format!("Failed to request PTY: {}", e)));
+        return Err(make_error(output, &format!("Failed to request PTY: {}", e)));
     }
 
     // Start the shell
     if let Err(e) = channel.shell() {
-        return Err(make_error(&output, &format!("Failed to start shell: {}", e)));
+        return Err(make_error(output, &format!("Failed to start shell: {}", e)));
     }
 
-    // Give shell time to initialize
-    std::thread::sleep(Duration::from_millis(1000));
-
-    // Read initial shell output (welcome message, prompt, etc)
-    let mut initial_buf = vec![0u8; 4096];
+    // Wait for the prompt, skipping the welcome banner, and remember it so
+    // commands know when they're done
     sess.set_blocking(false);
-    std::thread::sleep(Duration::from_millis(500));
-
-    match channel.read(&mut initial_buf) {
-        Ok(n) => {
-            let initial_output = String::from_utf8_lossy(&initial_buf[0..n]);
-            let cleaned = strip_ansi_codes(&initial_output);
-
-            // Extract only the prompt (last line that ends with #)
-            if let Some(prompt_line) = cleaned.lines().filter(|line| line.trim().ends_with('#')).last() {
-                let prompt_output = format!("{}\n", prompt_line.trim());
-                output.push_str(&prompt_output);
-                if let Some(sender) = &progress_tx {
-                    sender.send(prompt_output).ok();
-                }
-            }
-        }
-        _ => {}
+    let initial = read_until_prompt(&mut channel, None, PROMPT_TIMEOUT, false, output, progress_tx, cancel);
+    let prompt = trailing_prompt(&initial.text)
+        .filter(|_| initial.prompt_seen)
+        .map(str::to_string);
+    match &prompt {
+        Some(prompt) => send_output(output, &format!("{}\n", prompt), progress_tx),
+        None => send_output(output, "No shell prompt seen, carrying on\n", progress_tx),
     }
 
-    // Now send the set-inform command
-    let command = format!("set-inform {}/inform\n", controller_url);
-    if let Err(e) = channel.write_all(command.as_bytes()) {
-        return Err(make_error(&output, &format!("Failed to send command: {}", e)));
+    Ok(Shell { _session: sess, channel, prompt })
+}
+
+// Send `command` to the shell and return what it printed, up to the next
+// prompt. The output is also appended to the session log.
+fn run_shell_command(
+    shell: &mut Shell,
+    command: &str,
+    output: &mut String,
+    progress_tx: &Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    cancel: &CancelToken,
+) -> Result<String, String> {
+    shell
+        .channel
+        .write_all(format!("{}\n", command).as_bytes())
+        .map_err(|e| format!("Failed to send command: {}", e))?;
+    shell.channel.flush().ok();
+
+    let result = read_until_prompt(
+        &mut shell.channel,
+        shell.prompt.as_deref(),
+        COMMAND_TIMEOUT,
+        true,
+        output,
+        progress_tx,
+        cancel,
+    );
+    if !result.prompt_seen && !cancel.is_cancelled() && !shell.channel.eof() {
+        let msg = format!("\n(no prompt after {}s, carrying on)\n", COMMAND_TIMEOUT.as_secs());
+        send_output(output, &msg, progress_tx);
     }
-    channel.flush().ok();
 
-    // Wait a bit for command to execute
-    std::thread::sleep(Duration::from_millis(500));
+    Ok(result.text)
+}
 
-    // Read command output
-    let mut output_buf = vec![0u8; 4096];
-    let start = std::time::Instant::now();
-    while start.elapsed() < Duration::from_secs(5) {
-        match channel.read(&mut output_buf) {
-            Ok(n) if n > 0 => {
-                let cmd_output = String::from_utf8_lossy(&output_buf[0..n]);
-                send_output(&mut output, &cmd_output, &progress_tx);
+// Run `info`, falling back to `mca-cli-op info` on firmware without the alias
+fn query_info(
+    shell: &mut Shell,
+    output: &mut String,
+    progress_tx: &Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    cancel: &CancelToken,
+) -> Option<DeviceInfo> {
+    for command in ["info", "mca-cli-op info"] {
+        if cancel.is_cancelled() {
+            break;
+        }
+        if let Ok(text) = run_shell_command(shell, command, output, progress_tx, cancel) {
+            if let Some(info) = parse_info(&text) {
+                return Some(info);
             }
-            _ => {}
         }
-        std::thread::sleep(Duration::from_millis(100));
+    }
+    None
+}
 
-        if channel.eof() {
-            break;
-        }
+// Close the shell and return `Cancelled` if the caller asked us to stop
+fn check_cancelled(
+    shell: &mut Shell,
+    output: &mut String,
+    progress_tx: &Option<tokio::sync::mpsc::UnboundedSender<String>>,
+    cancel: &CancelToken,
+) -> Result<(), AdoptionError> {
+    if !cancel.is_cancelled() {
+        return Ok(());
     }
+    send_output(output, "\nCancelled, closing session\n", progress_tx);
+    close_shell(&mut shell.channel);
+    Err(AdoptionError::Cancelled(output.clone()))
+}
 
-    // Close the session
+fn close_shell(channel: &mut Channel) {
     channel.send_eof().ok();
     channel.wait_eof().ok();
     channel.close().ok();
     channel.wait_close().ok();
+}
+
+fn connect(host: &str, port: u16) -> Result<Session, String> {
+    let addr = crate::network_interface::socket_addr(host, port)?;
+    let tcp = TcpStream::connect_timeout(&addr, Duration::from_secs(10))
+        .map_err(|e| format!("Connection failed: {}", e))?;
+
+    tcp.set_read_timeout(Some(Duration::from_secs(30)))
+        .map_err(|e| format!("Failed to set timeout: {}", e))?;
+
+    let mut sess = Session::new().map_err(|e| format!("Failed to create session: {}", e))?;
+    sess.set_tcp_stream(tcp);
+    sess.handshake()
+        .map_err(|e| format!("SSH handshake failed: {}", e))?;
+
+    Ok(sess)
+}
+
+fn host_key(sess: &Session) -> Option<HostKey> {
+    let (_, key_type) = sess.host_key()?;
+    let hash = sess.host_key_hash(HashType::Sha256)?;
+    let key_type = match key_type {
+        ssh2::HostKeyType::Rsa => "ssh-rsa",
+        ssh2::HostKeyType::Dss => "ssh-dss",
+        ssh2::HostKeyType::Ecdsa256 => "ecdsa-sha2-nistp256",
+        ssh2::HostKeyType::Ecdsa384 => "ecdsa-sha2-nistp384",
+        ssh2::HostKeyType::Ecdsa521 => "ecdsa-sha2-nistp521",
+        ssh2::HostKeyType::Ed25519 => "ssh-ed25519",
+        ssh2::HostKeyType::Unknown => "unknown",
+    };
+    Some(HostKey {
+        key_type: key_type.to_string(),
+        fingerprint: known_hosts::format_fingerprint(hash),
+    })
+}
+
+fn authenticate(
+    sess: &Session,
+    credentials: &Credentials,
+    output: &mut String,
+    tx: &Option<tokio::sync::mpsc::UnboundedSender<String>>,
+) -> Result<(), String> {
+    let username = credentials.username.as_str();
+    let has_key = !credentials.key_path.is_empty();
+    let mut errors = Vec::new();
 
-    Ok(output)
+    if credentials.use_agent {
+        send_output(output, "Trying ssh-agent\n", tx);
+        match sess.userauth_agent(username) {
+            Ok(()) if sess.authenticated() => return Ok(()),
+            Ok(()) => errors.push(String::from("ssh-agent: key rejected")),
+            Err(e) => errors.push(format!("ssh-agent: {}", e)),
+        }
+    }
+
+    if has_key {
+        send_output(output, &format!("Trying key {}\n", credentials.key_path), tx);
+        let passphrase = Some(credentials.key_passphrase.as_str()).filter(|p| !p.is_empty());
+        match sess.userauth_pubkey_file(username, None, Path::new(&credentials.key_path), passphrase) {
+            Ok(()) if sess.authenticated() => return Ok(()),
+            Ok(()) => errors.push(String::from("key: rejected")),
+            Err(e) => errors.push(format!("key: {}", e)),
+        }
+    }
+
+    // Fall back to the password, even an empty one, unless only key-based
+    // methods were configured
+    if !credentials.password.is_empty() || (!credentials.use_agent && !has_key) {
+        if credentials.use_agent || has_key {
+            send_output(output, "Trying password\n", tx);
+        }
+        match sess.userauth_password(username, &credentials.password) {
+            Ok(()) if sess.authenticated() => return Ok(()),
+            Ok(()) => errors.push(String::from("password: Invalid credentials")),
+            Err(e) => errors.push(format!("password: {}", e)),
+        }
+    }
+
+    Err(errors.join("; "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prompts() {
+        let session = strip_ansi_codes("Welcome to UniFi!\r\n\x1b[1mBZ.v4.3.20#\x1b[0m ");
+        assert_eq!(trailing_prompt(&session), Some("BZ.v4.3.20#"));
+        assert_eq!(trailing_prompt("info\nModel: UAP-AC-Lite\nadmin@US-8:~$ "), Some("admin@US-8:~$"));
+        assert_eq!(trailing_prompt("UBNT-BZ.v4.3.20# "), Some("UBNT-BZ.v4.3.20#"));
+    }
+
+    #[test]
+    fn not_prompts() {
+        assert_eq!(trailing_prompt("# comment\n"), None);
+        assert_eq!(trailing_prompt("Adoption request sent to 'http://10.0.0.1:8080/inform'"), None);
+        assert_eq!(trailing_prompt(""), None);
+    }
 }
@@ -1,16 +1,18 @@
 use iced::{
     alignment,
     widget::{button, column, container, row, text, text_editor, text_input},
-    Element, Length,
+    Color, Element, Length,
 };
 
 use crate::messages::Message;
 use crate::styles::{terminal_style, SecondaryButtonStyle};
+use unifi_adopt::known_hosts::HostKey;
 
 pub fn main_view<'a>(
     ip_address: &str,
     log_editor_content: &'a text_editor::Content,
     is_running: bool,
+    changed_host_key: Option<&HostKey>,
 ) -> Element<'a, Message> {
     let header = row![
         text("Manual Device Entry")
@@ -24,7 +26,7 @@ pub fn main_view<'a>(
     .align_items(alignment::Alignment::Center)
     .spacing(10);
 
-    let ip_label = text("Device IP Address").size(14);
+    let ip_label = text("Device IP Address (add :port for SSH on another port)").size(14);
 
     let ip_input = text_input("192.168.1.20", ip_address)
         .on_input(Message::IpAddressChanged)
@@ -33,15 +35,17 @@ pub fn main_view<'a>(
 
     let adopt_button = if is_running {
         button(
-            text("Adopting...")
+            text("Cancel")
                 .size(16)
                 .horizontal_alignment(alignment::Horizontal::Center),
         )
+        .on_press(Message::CancelAdoption)
         .padding([12, 24])
         .width(Length::Fill)
+        .style(iced::theme::Button::custom(SecondaryButtonStyle))
     } else {
         button(
-            text("Adopt Default (ubnt)")
+            text("Adopt")
                 .size(16)
                 .horizontal_alignment(alignment::Horizontal::Center),
         )
@@ -50,24 +54,29 @@ pub fn main_view<'a>(
         .width(Length::Fill)
     };
 
-    let adopt_again_button = if is_running {
-        button(
-            text("Adopting...")
-                .size(16)
-                .horizontal_alignment(alignment::Horizontal::Center),
-        )
-        .padding([12, 24])
-        .width(Length::Fill)
-    } else {
-        button(
-            text("Adopt (alternate credentials)")
-                .size(16)
-                .horizontal_alignment(alignment::Horizontal::Center),
-        )
-        .on_press(Message::AdoptAgainClicked)
-        .padding([12, 24])
-        .width(Length::Fill)
-    };
+    let mut adopt_section = column![
+        ip_label,
+        ip_input,
+        adopt_button,
+    ]
+    .spacing(8);
+
+    if let Some(key) = changed_host_key {
+        adopt_section = adopt_section.push(
+            row![
+                text(format!("Host key changed, device now presents {}", key))
+                    .size(13)
+                    .style(Color::from_rgb(0.8, 0.2, 0.0))
+                    .width(Length::Fill),
+                button(text("Trust New Host Key").size(13))
+                    .on_press(Message::TrustManualHostKey)
+                    .padding([8, 16])
+                    .style(iced::theme::Button::custom(SecondaryButtonStyle)),
+            ]
+            .spacing(10)
+            .align_items(alignment::Alignment::Center),
+        );
+    }
 
     let logs_label = text("Console Output").size(14);
 
@@ -84,15 +93,7 @@ pub fn main_view<'a>(
 
     let content = column![
         header,
-        container(
-            column![
-                ip_label,
-                ip_input,
-                adopt_button,
-                adopt_again_button,
-            ]
-            .spacing(8)
-        ).padding([10, 0]),
+        container(adopt_section).padding([10, 0]),
         container(
             column![
                 logs_label,
@@ -1,19 +1,29 @@
 use iced::{
     alignment,
-    widget::{button, checkbox, column, container, mouse_area, row, scrollable, text, text_input},
+    widget::{button, checkbox, column, container, mouse_area, pick_list, row, scrollable, text, text_input},
     Background, Color, Element, Length, Theme,
 };
 
-use crate::messages::Message;
-use crate::models::{Device, DeviceStatus};
+use crate::messages::{InterfaceChoice, Message};
+use unifi_adopt::network_interface::{join_host_port, NetworkInterface};
+use unifi_adopt::{Device, DeviceStatus};
 use crate::styles::{terminal_style, SecondaryButtonStyle};
 
+#[allow(clippy::too_many_arguments)]
 pub fn scan_view(
-    ip_range_start: &str,
-    ip_range_end: &str,
+    scan_targets: &str,
+    scan_error: Option<&str>,
+    interfaces: &[NetworkInterface],
+    scan_interface: &InterfaceChoice,
     devices: &[Device],
     expanded_device_index: Option<usize>,
     is_scanning: bool,
+    scan_progress: (usize, usize),
+    is_listening: bool,
+    listen_error: Option<&str>,
+    is_serving_inform: bool,
+    inform_port: u16,
+    inform_error: Option<&str>,
 ) -> Element<'static, Message> {
     let title = row![
         text("Network Scanner")
@@ -35,27 +45,45 @@ pub fn scan_view(
         button(
             row![
                 text("⟳").size(14),
-                text(" Scanning...").size(14)
+                text(" Cancel Scan").size(14)
             ]
             .spacing(5)
             .align_items(alignment::Alignment::Center)
         )
+        .on_press(Message::CancelScan)
         .padding([10, 20])
+        .style(iced::theme::Button::custom(SecondaryButtonStyle))
     } else {
         button(text("Scan").size(14))
             .on_press(Message::ScanDevices)
             .padding([10, 20])
     };
 
-    let ip_range_section = column![
-        text("IP Range").size(14),
+    let (probed, total) = scan_progress;
+    let range_label = if is_scanning && total > 0 {
+        format!("Targets - probed {} of {} addresses, {} found", probed, total, devices.len())
+    } else {
+        String::from("Targets")
+    };
+
+    let interface_choices: Vec<InterfaceChoice> = std::iter::once(InterfaceChoice::Automatic)
+        .chain(interfaces.iter().cloned().map(InterfaceChoice::Interface))
+        .collect();
+
+    let mut ip_range_section = column![
         row![
-            text_input("192.168.1.1", ip_range_start)
-                .on_input(Message::IpRangeStartChanged)
-                .padding(10)
-                .size(14),
-            text_input("192.168.1.254", ip_range_end)
-                .on_input(Message::IpRangeEndChanged)
+            text("Interface").size(14),
+            pick_list(interface_choices, Some(scan_interface.clone()), Message::InterfaceSelected)
+                .padding(8)
+                .text_size(13),
+        ]
+        .spacing(10)
+        .align_items(alignment::Alignment::Center),
+        text(range_label).size(14),
+        row![
+            text_input("192.168.1.0/24, 10.0.0.10-50, !192.168.1.1", scan_targets)
+                .on_input(Message::ScanTargetsChanged)
+                .on_submit(Message::ScanDevices)
                 .padding(10)
                 .size(14),
             scan_button
@@ -65,28 +93,85 @@ pub fn scan_view(
     ]
     .spacing(6);
 
-    let device_list_section = if is_scanning {
+    if let Some(error) = scan_error {
+        ip_range_section = ip_range_section.push(
+            text(error.to_string()).size(13).style(Color::from_rgb(0.8, 0.2, 0.2))
+        );
+    }
+
+    let ip_range_section = ip_range_section
+        .push(
+            row![
+                checkbox("Listen for device announcements", is_listening)
+                    .on_toggle(Message::ListenToggled)
+                    .size(16)
+                    .text_size(13),
+                text(listen_error.unwrap_or_default().to_string())
+                    .size(13)
+                    .style(Color::from_rgb(0.8, 0.2, 0.2)),
+            ]
+            .spacing(15)
+            .align_items(alignment::Alignment::Center),
+        )
+        .push(
+            row![
+                checkbox(format!("Accept informs on port {}", inform_port), is_serving_inform)
+                    .on_toggle(Message::InformToggled)
+                    .size(16)
+                    .text_size(13),
+                text(inform_error.unwrap_or_default().to_string())
+                    .size(13)
+                    .style(Color::from_rgb(0.8, 0.2, 0.2)),
+            ]
+            .spacing(15)
+            .align_items(alignment::Alignment::Center),
+        );
+
+    // Rows are shown as soon as the first host answers
+    let device_list_section = if is_scanning && devices.is_empty() {
         build_scanning_view()
     } else {
         build_full_view(devices, expanded_device_index)
     };
 
-    let adopt_buttons = row![
-        button(text("Adopt Default (ubnt)").size(14).horizontal_alignment(alignment::Horizontal::Center))
-            .on_press(Message::AdoptSelectedDefault)
-            .padding([10, 20])
-            .width(Length::Fill),
-        button(text("Adopt (alternate credentials)").size(14).horizontal_alignment(alignment::Horizontal::Center))
-            .on_press(Message::AdoptSelectedAlt)
-            .padding([10, 20])
-            .width(Length::Fill),
-    ]
-    .spacing(10);
+    // Queue progress, shown while devices are waiting or running
+    let running = devices.iter().filter(|d| d.status == DeviceStatus::InProgress).count();
+    let queued = devices.iter().filter(|d| d.status == DeviceStatus::Queued).count();
+    let queue_status = if running + queued > 0 {
+        format!("{} running, {} queued", running, queued)
+    } else {
+        String::new()
+    };
+
+    // Jobs can't start until the scan has finished adding rows
+    let mut identify_button = button(text("Identify Selected").size(14).horizontal_alignment(alignment::Horizontal::Center))
+        .padding([10, 20])
+        .width(Length::Fill)
+        .style(iced::theme::Button::custom(SecondaryButtonStyle));
+    let mut adopt_button = button(text("Adopt Selected").size(14).horizontal_alignment(alignment::Horizontal::Center))
+        .padding([10, 20])
+        .width(Length::Fill);
+    if !is_scanning {
+        identify_button = identify_button.on_press(Message::IdentifySelected);
+        adopt_button = adopt_button.on_press(Message::AdoptSelected);
+    }
+
+    let mut adopt_buttons = row![identify_button, adopt_button].spacing(10);
+
+    if running + queued > 0 {
+        adopt_buttons = adopt_buttons.push(
+            button(text("Cancel All").size(14).horizontal_alignment(alignment::Horizontal::Center))
+                .on_press(Message::CancelAllJobs)
+                .padding([10, 20])
+                .style(iced::theme::Button::custom(SecondaryButtonStyle)),
+        );
+    }
 
     let content = column![
         title,
         ip_range_section,
         container(device_list_section).padding([10, 0]),
+        text(queue_status).size(13).style(Color::from_rgb(0.4, 0.4, 0.4)),
         adopt_buttons,
     ]
     .spacing(15)
@@ -106,7 +191,10 @@ fn build_full_view(devices: &[Device], expanded_device_index: Option<usize>) ->
             container(text("")).width(Length::Fixed(40.0)),
             container(text("IP Address").size(13)).width(Length::FillPortion(2)),
             container(text("MAC Address").size(13)).width(Length::FillPortion(2)),
-            container(text("Company").size(13)).width(Length::FillPortion(3)),
+            container(text("Hostname").size(13)).width(Length::FillPortion(2)),
+            container(text("Company").size(13)).width(Length::FillPortion(2)),
+            container(text("Model").size(13)).width(Length::FillPortion(2)),
+            container(text("Inform").size(13)).width(Length::FillPortion(2)),
             container(text("Status").size(13)).width(Length::Fixed(60.0)),
         ]
         .padding(10)
@@ -231,7 +319,7 @@ fn build_device_row(
     let is_expanded = Some(index) == expanded_device_index;
     let row_bg = if is_expanded {
         Color::from_rgb(0.85, 0.90, 0.95)
-    } else if row_num % 2 == 0 {
+    } else if row_num.is_multiple_of(2) {
         Color::from_rgb(0.99, 0.99, 0.99)
     } else {
         Color::from_rgb(0.96, 0.96, 0.96)
@@ -239,6 +327,23 @@ fn build_device_row(
 
     let status_icon = build_status_icon(&device.status);
 
+    // The port is only worth showing when it isn't the usual 22
+    let address = match device.ssh_port() {
+        Some(port) => join_host_port(&device.ip, port),
+        None => device.ip.clone(),
+    };
+    let rtt_text = device
+        .rtt
+        .map(|rtt| format!("{:.1} ms", rtt.as_secs_f64() * 1000.0))
+        .unwrap_or_default();
+
+    let hostname = device.hostname.as_deref().unwrap_or_default();
+    let hostname_text = if hostname.chars().count() > 30 {
+        format!("{}...", hostname.chars().take(27).collect::<String>())
+    } else {
+        hostname.to_string()
+    };
+
     let mut row_content = row![
         container(
             checkbox("", device.selected)
@@ -246,8 +351,15 @@ fn build_device_row(
         )
         .width(Length::Fixed(40.0))
         .center_x(),
-        container(text(&device.ip).size(13)).width(Length::FillPortion(2)),
+        container(
+            column![
+                text(address).size(13),
+                text(rtt_text).size(11).style(Color::from_rgb(0.5, 0.5, 0.5)),
+            ]
+        )
+        .width(Length::FillPortion(2)),
         container(text(&device.mac).size(13)).width(Length::FillPortion(2)),
+        container(text(hostname_text).size(13)).width(Length::FillPortion(2)),
     ]
     .spacing(10)
     .padding(8);
@@ -259,10 +371,59 @@ fn build_device_row(
             device.company.clone()
         };
         row_content = row_content.push(
-            container(text(company_text).size(13)).width(Length::FillPortion(3))
+            container(
+                column![
+                    text(company_text).size(13),
+                    text(device.kind().label()).size(11).style(Color::from_rgb(0.5, 0.5, 0.5)),
+                ]
+            )
+            .width(Length::FillPortion(2))
         );
     }
 
+    // Filled in once the device has been identified over SSH, or from its
+    // discovery reply until then
+    let grey = Color::from_rgb(0.3, 0.3, 0.3);
+    let (model_text, version_text, inform_text, inform_color) = match (&device.info, &device.discovery) {
+        (Some(info), _) => {
+            let color = if info.is_connected() {
+                Color::from_rgb(0.0, 0.6, 0.0)
+            } else {
+                grey
+            };
+            (info.model.clone(), info.version.clone(), info.inform_state().to_string(), color)
+        }
+        (None, Some(found)) => {
+            let model = if found.model.is_empty() { &found.platform } else { &found.model };
+            let inform = match found.is_default {
+                Some(true) => "Factory default",
+                Some(false) => "Adopted",
+                None => "",
+            };
+            (model.clone(), found.display_version().to_string(), inform.to_string(), grey)
+        }
+        (None, None) => (String::new(), String::new(), String::new(), grey),
+    };
+    row_content = row_content.push(
+        container(
+            column![
+                text(model_text).size(13),
+                text(version_text).size(11).style(Color::from_rgb(0.5, 0.5, 0.5)),
+            ]
+        )
+        .width(Length::FillPortion(2))
+    );
+    let inform_received_text = if device.inform_received { "Inform received" } else { "" };
+    row_content = row_content.push(
+        container(
+            column![
+                text(inform_text).size(13).style(inform_color),
+                text(inform_received_text).size(11).style(Color::from_rgb(0.0, 0.6, 0.0)),
+            ]
+        )
+        .width(Length::FillPortion(2))
+    );
+
     row_content = row_content.push(
         container(status_icon)
             .width(Length::Fixed(60.0))
@@ -297,13 +458,44 @@ fn build_device_row(
         .style(terminal_style)
         .width(Length::Fill);
 
-        let logs_section = container(
-            column![
-                text(format!("Logs for {}", device.ip)).size(14),
-                logs_box,
-            ]
-            .spacing(8)
-        )
+        let mut logs_column = column![].spacing(8);
+        if !device.ports.is_empty() {
+            let ports = device.ports.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", ");
+            logs_column = logs_column.push(
+                text(format!("Open ports: {}", ports)).size(13).style(Color::from_rgb(0.3, 0.3, 0.3)),
+            );
+        }
+        logs_column = logs_column
+            .push(text(format!("Logs for {}", device.ip)).size(14))
+            .push(logs_box);
+
+        if matches!(device.status, DeviceStatus::Queued | DeviceStatus::InProgress) {
+            logs_column = logs_column.push(
+                button(text("Cancel").size(13))
+                    .on_press(Message::CancelDevice(index))
+                    .padding([8, 16])
+                    .style(iced::theme::Button::custom(SecondaryButtonStyle)),
+            );
+        }
+
+        if let Some((_, key)) = &device.changed_host_key {
+            logs_column = logs_column.push(
+                row![
+                    text(format!("Device now presents {}", key))
+                        .size(13)
+                        .style(Color::from_rgb(0.8, 0.2, 0.0))
+                        .width(Length::Fill),
+                    button(text("Trust New Host Key").size(13))
+                        .on_press(Message::TrustHostKey(index))
+                        .padding([8, 16])
+                        .style(iced::theme::Button::custom(SecondaryButtonStyle)),
+                ]
+                .spacing(10)
+                .align_items(alignment::Alignment::Center),
+            );
+        }
+
+        let logs_section = container(logs_column)
         .padding([8, 24, 8, 24])
         .style(move |_theme: &Theme| container::Appearance {
             background: Some(Background::Color(row_bg)),
@@ -324,6 +516,12 @@ fn build_status_icon(status: &DeviceStatus) -> Element<'static, Message> {
         DeviceStatus::Pending => {
             text("").into()
         }
+        DeviceStatus::Queued => {
+            text("…")
+                .size(18)
+                .style(Color::from_rgb(0.5, 0.5, 0.5))
+                .into()
+        }
         DeviceStatus::InProgress => {
             text("⟳")
                 .size(18)
@@ -336,12 +534,24 @@ fn build_status_icon(status: &DeviceStatus) -> Element<'static, Message> {
                 .style(Color::from_rgb(0.0, 0.6, 0.0))
                 .into()
         }
+        DeviceStatus::Unconfirmed => {
+            text("?")
+                .size(18)
+                .style(Color::from_rgb(0.85, 0.55, 0.0))
+                .into()
+        }
         DeviceStatus::Error => {
             text("⚠")
                 .size(18)
                 .style(Color::from_rgb(0.8, 0.2, 0.0))
                 .into()
         }
+        DeviceStatus::Cancelled => {
+            text("✕")
+                .size(18)
+                .style(Color::from_rgb(0.5, 0.5, 0.5))
+                .into()
+        }
     }
 }
 
@@ -1,25 +1,35 @@
 use iced::{
     alignment,
-    widget::{button, column, container, row, text, text_input},
+    widget::{button, checkbox, column, container, pick_list, row, scrollable, text, text_input},
     Background, Border, Color, Element, Length, Theme,
 };
 
-use crate::messages::Message;
-use crate::models::SettingsTab;
+use crate::messages::{Message, SettingsTab};
 use crate::styles::SecondaryButtonStyle;
+use unifi_adopt::config::{Credentials, HostKeyPolicy};
 
+#[allow(clippy::too_many_arguments)]
 pub fn settings_view(
     active_tab: &SettingsTab,
     controller_url_input: &str,
-    username_input: &str,
-    password_input: &str,
-    alt_username_input: &str,
-    alt_password_input: &str,
+    ssh_port_input: &str,
+    credentials_input: &[Credentials],
+    host_key_policy_input: HostKeyPolicy,
+    verify_adoption_input: bool,
+    verify_timeout_input: &str,
+    reinform_input: bool,
+    reinform_interval_input: &str,
+    max_parallel_jobs_input: &str,
+    scan_max_in_flight_input: &str,
+    scan_rate_input: &str,
+    scan_ipv6_input: bool,
+    dhcp_lease_file_input: &str,
+    scan_ports_input: &str,
 ) -> Element<'static, Message> {
     let title = text("Settings").size(24);
 
     let general_label = if *active_tab == SettingsTab::General {
-        format!("→ General")
+        "→ General".to_string()
     } else {
         "General".to_string()
     };
@@ -33,37 +43,22 @@ pub fn settings_view(
     .padding([10, 20])
     .width(Length::Fill);
 
-    let default_label = if *active_tab == SettingsTab::DefaultCredentials {
-        format!("→ Default Credentials")
+    let creds_label = if *active_tab == SettingsTab::Credentials {
+        "→ Credentials".to_string()
     } else {
-        "Default Credentials".to_string()
+        "Credentials".to_string()
     };
 
-    let default_creds_tab = button(
-        text(default_label)
+    let creds_tab = button(
+        text(creds_label)
             .size(14)
             .horizontal_alignment(alignment::Horizontal::Center)
     )
-    .on_press(Message::TabSelected(SettingsTab::DefaultCredentials))
+    .on_press(Message::TabSelected(SettingsTab::Credentials))
     .padding([10, 20])
     .width(Length::Fill);
 
-    let alt_label = if *active_tab == SettingsTab::AlternativeCredentials {
-        format!("→ Alternative Credentials")
-    } else {
-        "Alternative Credentials".to_string()
-    };
-
-    let alt_creds_tab = button(
-        text(alt_label)
-            .size(14)
-            .horizontal_alignment(alignment::Horizontal::Center)
-    )
-    .on_press(Message::TabSelected(SettingsTab::AlternativeCredentials))
-    .padding([10, 20])
-    .width(Length::Fill);
-
-    let tabs = row![general_tab, default_creds_tab, alt_creds_tab]
+    let tabs = row![general_tab, creds_tab]
         .spacing(5)
         .padding([0, 0, 15, 0]);
 
@@ -79,57 +74,168 @@ pub fn settings_view(
                         .size(14),
                 ]
                 .spacing(6),
-            ]
-            .spacing(15)
-        }
-        SettingsTab::DefaultCredentials => {
-            column![
-                text("Default SSH Credentials (ubnt)").size(16),
-                text("These credentials are used with the 'Adopt Default (ubnt)' button")
-                    .size(12),
                 column![
-                    text("SSH Username").size(13),
-                    text_input("ubnt", username_input)
-                        .on_input(Message::UsernameChanged)
-                        .padding(10)
-                        .size(14),
+                    row![
+                        text("SSH port").size(13),
+                        text_input("22", ssh_port_input)
+                            .on_input(Message::SshPortChanged)
+                            .padding(8)
+                            .size(14)
+                            .width(Length::Fixed(80.0)),
+                    ]
+                    .spacing(10)
+                    .align_items(alignment::Alignment::Center),
+                    text("Used unless the device address (192.168.1.20:2222) or the credential set names a port. Scans also look for SSH here")
+                        .size(12),
                 ]
                 .spacing(6),
                 column![
-                    text("SSH Password").size(13),
-                    text_input("ubnt", password_input)
-                        .on_input(Message::PasswordChanged)
-                        .padding(10)
-                        .size(14),
+                    text("SSH Host Key Verification").size(13),
+                    pick_list(
+                        &HostKeyPolicy::ALL[..],
+                        Some(host_key_policy_input),
+                        Message::HostKeyPolicySelected,
+                    )
+                    .padding(10)
+                    .text_size(14),
+                    text("Changed keys always block adoption unless verification is off")
+                        .size(12),
                 ]
                 .spacing(6),
-            ]
-            .spacing(15)
-        }
-        SettingsTab::AlternativeCredentials => {
-            column![
-                text("Alternative SSH Credentials").size(16),
-                text("These credentials are used with the 'Adopt Again' button for non-default setups")
-                    .size(12),
                 column![
-                    text("SSH Username").size(13),
-                    text_input("admin", alt_username_input)
-                        .on_input(Message::AltUsernameChanged)
-                        .padding(10)
-                        .size(14),
+                    checkbox("Confirm the device reaches the controller after set-inform", verify_adoption_input)
+                        .on_toggle(Message::VerifyAdoptionToggled)
+                        .size(16)
+                        .text_size(13),
+                    row![
+                        text("Give up after (seconds)").size(13),
+                        text_input("60", verify_timeout_input)
+                            .on_input(Message::VerifyTimeoutChanged)
+                            .padding(8)
+                            .size(14)
+                            .width(Length::Fixed(80.0)),
+                    ]
+                    .spacing(10)
+                    .align_items(alignment::Alignment::Center),
                 ]
                 .spacing(6),
                 column![
-                    text("SSH Password").size(13),
-                    text_input("password", alt_password_input)
-                        .on_input(Message::AltPasswordChanged)
-                        .padding(10)
+                    checkbox("Re-send set-inform until the device is connected", reinform_input)
+                        .on_toggle(Message::ReinformToggled)
+                        .size(16)
+                        .text_size(13),
+                    row![
+                        text("Every (seconds)").size(13),
+                        text_input("20", reinform_interval_input)
+                            .on_input(Message::ReinformIntervalChanged)
+                            .padding(8)
+                            .size(14)
+                            .width(Length::Fixed(80.0)),
+                    ]
+                    .spacing(10)
+                    .align_items(alignment::Alignment::Center),
+                    text("Approve the device in the controller while this runs. Uses the timeout above.")
+                        .size(12),
+                ]
+                .spacing(6),
+                column![
+                    row![
+                        text("Devices to adopt at once").size(13),
+                        text_input("8", max_parallel_jobs_input)
+                            .on_input(Message::MaxParallelJobsChanged)
+                            .padding(8)
+                            .size(14)
+                            .width(Length::Fixed(80.0)),
+                    ]
+                    .spacing(10)
+                    .align_items(alignment::Alignment::Center),
+                    text("Further selected devices wait in a queue and start as others finish")
+                        .size(12),
+                ]
+                .spacing(6),
+                text("Network Scan").size(16),
+                column![
+                    row![
+                        text("Addresses to probe at once").size(13),
+                        text_input("64", scan_max_in_flight_input)
+                            .on_input(Message::ScanMaxInFlightChanged)
+                            .padding(8)
+                            .size(14)
+                            .width(Length::Fixed(80.0)),
+                    ]
+                    .spacing(10)
+                    .align_items(alignment::Alignment::Center),
+                    row![
+                        text("Packets per second (0 = no limit)").size(13),
+                        text_input("0", scan_rate_input)
+                            .on_input(Message::ScanRateChanged)
+                            .padding(8)
+                            .size(14)
+                            .width(Length::Fixed(80.0)),
+                    ]
+                    .spacing(10)
+                    .align_items(alignment::Alignment::Center),
+                    text("Lower these on customer networks with intrusion detection")
+                        .size(12),
+                ]
+                .spacing(6),
+                column![
+                    row![
+                        text("Ports to probe").size(13),
+                        text_input("22,80,443,8080,8443,10001", scan_ports_input)
+                            .on_input(Message::ScanPortsChanged)
+                            .padding(8)
+                            .size(14)
+                            .width(Length::Fixed(240.0)),
+                    ]
+                    .spacing(10)
+                    .align_items(alignment::Alignment::Center),
+                    text("Live hosts are checked on each TCP port for an SSH banner or HTTP Server header. 10001 sends the UniFi discovery probe")
+                        .size(12),
+                ]
+                .spacing(6),
+                column![
+                    checkbox("Also find IPv6 devices", scan_ipv6_input)
+                        .on_toggle(Message::ScanIpv6Toggled)
+                        .size(16)
+                        .text_size(13),
+                    text("Pings ff02::1 and reads the IPv6 neighbor table on the scanned links. With this on, Targets may be left empty for IPv6-only networks")
+                        .size(12),
+                ]
+                .spacing(6),
+                column![
+                    text("DHCP Lease File").size(13),
+                    text_input("/var/lib/misc/dnsmasq.leases", dhcp_lease_file_input)
+                        .on_input(Message::DhcpLeaseFileChanged)
+                        .padding(8)
                         .size(14),
+                    text("Optional. Hostnames from a dnsmasq or ISC dhcpd lease file label the devices it has leased to; others are looked up with reverse DNS and mDNS")
+                        .size(12),
                 ]
                 .spacing(6),
             ]
             .spacing(15)
         }
+        SettingsTab::Credentials => {
+            let mut sets = column![
+                text("SSH Credential Sets").size(16),
+                text("Adoption tries each set from top to bottom until one is accepted")
+                    .size(12),
+            ]
+            .spacing(15);
+
+            let count = credentials_input.len();
+            for (index, creds) in credentials_input.iter().enumerate() {
+                sets = sets.push(build_credential_set(index, count, creds));
+            }
+
+            sets.push(
+                button(text("Add Credential Set").size(13))
+                    .on_press(Message::AddCredential)
+                    .padding([8, 16])
+                    .style(iced::theme::Button::custom(SecondaryButtonStyle)),
+            )
+        }
     };
 
     let buttons = row![
@@ -146,7 +252,7 @@ pub fn settings_view(
     let content = column![
         title,
         tabs,
-        container(tab_content)
+        container(scrollable(tab_content))
             .padding(20)
             .style(|_theme: &Theme| container::Appearance {
                 background: Some(Background::Color(Color::from_rgb(0.98, 0.98, 0.98))),
@@ -158,7 +264,7 @@ pub fn settings_view(
                 ..Default::default()
             })
             .width(Length::Fill)
-            .height(Length::Fixed(260.0)),
+            .height(Length::Fixed(320.0)),
         buttons,
     ]
     .spacing(15)
@@ -172,3 +278,107 @@ pub fn settings_view(
         .center_y()
         .into()
 }
+
+fn build_credential_set(index: usize, count: usize, creds: &Credentials) -> Element<'static, Message> {
+    let mut move_up = button(text("↑").size(13))
+        .padding([6, 10])
+        .style(iced::theme::Button::custom(SecondaryButtonStyle));
+    if index > 0 {
+        move_up = move_up.on_press(Message::MoveCredentialUp(index));
+    }
+
+    let mut move_down = button(text("↓").size(13))
+        .padding([6, 10])
+        .style(iced::theme::Button::custom(SecondaryButtonStyle));
+    if index + 1 < count {
+        move_down = move_down.on_press(Message::MoveCredentialDown(index));
+    }
+
+    let header = row![
+        text(format!("{}.", index + 1)).size(14),
+        text_input("Name", &creds.name)
+            .on_input(move |value| Message::CredentialNameChanged(index, value))
+            .padding(8)
+            .size(14),
+        move_up,
+        move_down,
+        button(text("Remove").size(13))
+            .on_press(Message::RemoveCredential(index))
+            .padding([6, 10])
+            .style(iced::theme::Button::custom(SecondaryButtonStyle)),
+    ]
+    .spacing(6)
+    .align_items(alignment::Alignment::Center);
+
+    let fields = column![
+        header,
+        row![
+            column![
+                text("SSH Username").size(13),
+                text_input("ubnt", &creds.username)
+                    .on_input(move |value| Message::CredentialUsernameChanged(index, value))
+                    .padding(10)
+                    .size(14),
+            ]
+            .spacing(6),
+            column![
+                text("SSH Password").size(13),
+                text_input("ubnt", &creds.password)
+                    .on_input(move |value| Message::CredentialPasswordChanged(index, value))
+                    .padding(10)
+                    .size(14),
+            ]
+            .spacing(6),
+        ]
+        .spacing(10),
+        row![
+            column![
+                text("Private Key File (optional)").size(13),
+                text_input("~/.ssh/id_ed25519", &creds.key_path)
+                    .on_input(move |value| Message::CredentialKeyPathChanged(index, value))
+                    .padding(10)
+                    .size(14),
+            ]
+            .spacing(6),
+            column![
+                text("Key Passphrase").size(13),
+                text_input("", &creds.key_passphrase)
+                    .on_input(move |value| Message::CredentialKeyPassphraseChanged(index, value))
+                    .padding(10)
+                    .size(14),
+            ]
+            .spacing(6),
+        ]
+        .spacing(10),
+        row![
+            checkbox("Try keys from the running ssh-agent first", creds.use_agent)
+                .on_toggle(move |value| Message::CredentialUseAgentToggled(index, value))
+                .size(16)
+                .text_size(13)
+                .width(Length::Fill),
+            text("SSH Port").size(13),
+            text_input("default", &creds.port.map(|port| port.to_string()).unwrap_or_default())
+                .on_input(move |value| Message::CredentialPortChanged(index, value))
+                .padding(8)
+                .size(14)
+                .width(Length::Fixed(80.0)),
+        ]
+        .spacing(10)
+        .align_items(alignment::Alignment::Center),
+    ]
+    .spacing(10);
+
+    container(fields)
+        .padding(12)
+        .style(|_theme: &Theme| container::Appearance {
+            background: Some(Background::Color(Color::WHITE)),
+            border: Border {
+                color: Color::from_rgb(0.85, 0.85, 0.85),
+                width: 1.0,
+                radius: 4.0.into(),
+            },
+            ..Default::default()
+        })
+        .width(Length::Fill)
+        .into()
+}